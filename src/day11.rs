@@ -24,36 +24,45 @@ fn paint(code: Vec<Word>, starting_tile: Word) -> HashMap<Point, Word> {
     let mut current_pos = Point::zero();
     let mut current_facing = UP;
 
-    while let Err(ExecError::InputBlocked) = robot.run() {
-        for command in robot.out_buf.windows(2) {
-            let paint = command[0];
-            assert!(paint == BLACK || paint == WHITE);
-            tiles.insert(current_pos, paint);
-
-            current_facing = match command[1] {
-                TURN_LEFT => match current_facing {
-                    UP => LEFT,
-                    facing => facing - 1,
-                },
-                TURN_RIGHT => match current_facing {
-                    LEFT => UP,
-                    facing => facing + 1,
+    loop {
+        match robot.run_until(None).expect("robot program failed") {
+            StopReason::OutputProduced if robot.out_buf.len() == 2 => {
+                let paint = robot.out_buf[0];
+                assert!(paint == BLACK || paint == WHITE);
+                tiles.insert(current_pos, paint);
+
+                current_facing = match robot.out_buf[1] {
+                    TURN_LEFT => match current_facing {
+                        UP => LEFT,
+                        facing => facing - 1,
+                    },
+                    TURN_RIGHT => match current_facing {
+                        LEFT => UP,
+                        facing => facing + 1,
+                    }
+                    bad => panic!("invalid facing: {}", bad),
+                };
+
+                match current_facing {
+                    UP => current_pos.y -= 1,
+                    RIGHT => current_pos.x += 1,
+                    DOWN => current_pos.y += 1,
+                    LEFT => current_pos.x -= 1,
+                    _ => unreachable!(),
                 }
-                bad => panic!("invalid facing: {}", bad),
-            };
-
-            match current_facing {
-                UP => current_pos.y -= 1,
-                RIGHT => current_pos.x += 1,
-                DOWN => current_pos.y += 1,
-                LEFT => current_pos.x -= 1,
-                _ => unreachable!(),
+
+                robot.out_buf.clear();
             }
-        }
-        robot.out_buf.clear();
 
-        let current_color = tiles.get(&current_pos).cloned().unwrap_or(BLACK);
-        robot.in_buf.push(current_color);
+            StopReason::NeedsInput => {
+                let current_color = tiles.get(&current_pos).cloned().unwrap_or(BLACK);
+                robot.in_buf.push_back(current_color);
+            }
+
+            StopReason::Halted => break,
+
+            _ => continue,
+        }
     }
 
     tiles
@@ -3,6 +3,7 @@ use std::collections::HashMap;
 mod intcode;
 mod point;
 use point::*;
+use intcode::StopReason;
 use std::cmp::Ordering;
 
 const BLOCK: intcode::Word = 2;
@@ -36,33 +37,34 @@ impl Game {
 
     fn run<Joystick: Fn(&Self) -> JoyInput>(&mut self, joystick: Joystick) {
         loop {
-            let result = self.computer.run();
-
-            for output in self.computer.out_buf.chunks(3) {
-                let pos = Point::new(output[0], output[1]);
-                let id = output[2];
-
-                if pos == Point::new(-1, 0) {
-                    self.score = id;
-                } else {
-                    self.screen.insert(pos, id);
+            match self.computer.run_until(None).expect("arcade program failed") {
+                StopReason::OutputProduced if self.computer.out_buf.len() == 3 => {
+                    let output = &self.computer.out_buf;
+                    let pos = Point::new(output[0], output[1]);
+                    let id = output[2];
+
+                    if pos == Point::new(-1, 0) {
+                        self.score = id;
+                    } else {
+                        self.screen.insert(pos, id);
+                    }
+                    self.computer.out_buf.clear();
                 }
-            }
-            self.computer.out_buf.clear();
 
-            match result {
-                Err(intcode::ExecError::InputBlocked) => {
+                StopReason::NeedsInput => {
                     let input = joystick(self);
-                    self.computer.in_buf.push(match input {
+                    self.computer.in_buf.push_back(match input {
                         JoyInput::Neutral => 0,
                         JoyInput::Left => -1,
                         JoyInput::Right => 1,
                     });
                 }
 
-                Ok(()) => {
+                StopReason::Halted => {
                     break;
                 }
+
+                _ => continue,
             }
         }
     }
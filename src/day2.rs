@@ -1,23 +1,24 @@
 mod intcode;
 use intcode::{Computer, Word};
+use intcode::snapshot::Snapshot;
 
-fn exec_command(initial: Vec<Word>, noun: Word, verb: Word) -> Word {
-    let mut code = initial.to_vec();
-    code[1] = noun;
-    code[2] = verb;
+fn exec_command(computer: &mut Computer, initial: &Snapshot, noun: Word, verb: Word) -> Word {
+    computer.restore(initial);
+    computer.mem_store(1, noun);
+    computer.mem_store(2, verb);
 
-    let mut computer = Computer::new(code);
     computer.run().expect("should run until halt");
     computer.mem_load(0)
 }
 
 fn main() {
     let input = include_str!("day2.txt");
-    let code = intcode::from_str(input);
+    let mut computer = Computer::new(intcode::from_str(input));
+    let initial = computer.snapshot();
 
     const GRAVITY: Word = 12;
     const RESTORE: Word = 2;
-    let gravity_result = exec_command(code.clone(), GRAVITY, RESTORE);
+    let gravity_result = exec_command(&mut computer, &initial, GRAVITY, RESTORE);
     assert_eq!(4138687, gravity_result);
 
     println!(
@@ -29,7 +30,7 @@ fn main() {
 
     for noun in 0..=99 {
         for verb in 0..=99 {
-            if exec_command(code.clone(), noun, verb) == TARGET_STATE {
+            if exec_command(&mut computer, &initial, noun, verb) == TARGET_STATE {
                 let command_code = 100 * noun + verb;
 
                 println!("command code for result {}: {}", TARGET_STATE, command_code);
@@ -3,7 +3,7 @@ use intcode::{Word, Computer};
 
 fn input_one(code: Vec<Word>, input: Word) -> Computer {
     let mut computer = intcode::Computer::new(code);
-    computer.in_buf.push(input);
+    computer.in_buf.push_back(input);
     computer.run().expect("should run until halt");
     computer
 }
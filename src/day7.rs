@@ -4,7 +4,7 @@ use intcode::{Word, ExecError, Computer};
 
 fn new_amp(code: Vec<Word>, setting: Word) -> Computer {
     let mut computer = Computer::new(code);
-    computer.in_buf.push(setting);
+    computer.in_buf.push_back(setting);
     computer
 }
 
@@ -16,6 +16,8 @@ fn run_amp(amp: &mut Computer) -> Option<Word> {
             assert_eq!(amp.out_buf.len(), 1);
             Some(amp.out_buf[0])
         },
+
+        Err(err) => panic!("amplifier program failed: {}", err),
     }
 }
 
@@ -38,7 +40,7 @@ fn main() {
 
     for combination in (0 as Word..=4).permutations(5) {
         let mut amps = create_amps(&code, &combination);
-        amps[0].in_buf.push(0);
+        amps[0].in_buf.push_back(0);
 
         for i in 0..5 {
             let out = run_amp(&mut amps[i]).expect("should run until halt in part 1");
@@ -49,7 +51,7 @@ fn main() {
                     max_combination = combination.clone();
                 }
             } else {
-                amps[i + 1].in_buf.push(out);
+                amps[i + 1].in_buf.push_back(out);
             }
         }
     }
@@ -59,7 +61,7 @@ fn main() {
     for combination in (5 as Word..=9).permutations(5) {
         let mut amps = create_amps(&code, &combination);
         let mut final_values = [None; 5];
-        amps[0].in_buf.push(0);
+        amps[0].in_buf.push_back(0);
 
         let out = 'feedback_loop: loop {
             for i in 0..5 {
@@ -1,28 +1,37 @@
-use digits_iterator::*;
-
 mod intcode;
 use intcode::{Word, Computer};
+use intcode::word::{IntcodeWord, Checked};
+use num::BigInt;
 
-fn exec_one(code: Vec<Word>) -> Vec<Word> {
+fn exec_one<W: IntcodeWord>(code: Vec<W>) -> Vec<W> {
     let mut computer = Computer::new(code);
     computer.run().expect("should run until halt");
 
-    computer.out_buf
+    computer.out_buf.into()
 }
 
-fn main() {
-    assert_eq!(exec_one(intcode::from_str("104,1125899906842624,99")), [1125899906842624]);
+fn check_samples<W: IntcodeWord>() {
+    let big = exec_one(intcode::from_str_as::<W>("104,1125899906842624,99"));
+    assert_eq!(big, [W::from_i64(1125899906842624)]);
 
-    assert_eq!(exec_one(intcode::from_str("1102,34915192,34915192,7,4,7,99,0"))[0].digits().count(), 16);
+    let product = exec_one(intcode::from_str_as::<W>("1102,34915192,34915192,7,4,7,99,0"));
+    assert_eq!(product[0].to_string().len(), 16);
 
-    let quine = intcode::from_str("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99");
+    let quine = intcode::from_str_as::<W>("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99");
     assert_eq!(exec_one(quine.clone()), quine);
+}
+
+fn main() {
+    check_samples::<Word>();
+    check_samples::<i128>();
+    check_samples::<BigInt>();
+    check_samples::<Checked>();
 
     let input = include_str!("day9.txt");
     let code = intcode::from_str(input);
 
     let mut boost_computer = Computer::new(code);
-    boost_computer.in_buf.push(1);
+    boost_computer.in_buf.push_back(1);
     boost_computer.run().expect("should halt");
 
     println!("BOOST output: {:?}", boost_computer.out_buf);
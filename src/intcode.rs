@@ -1,27 +1,174 @@
 #![allow(unused)]
 
-use std::convert::TryInto;
-use digits_iterator;
-use digits_iterator::DigitsExtension;
+use std::convert::TryFrom;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::error::Error;
+use std::mem;
 
+pub mod word;
+use word::IntcodeWord;
+
+pub mod memory;
+use memory::Memory;
+
+pub mod load;
+pub use load::{ParseError, ParseMode, load_file, load_computer};
+
+pub mod disasm;
+pub mod asm;
+pub mod symbols;
+pub mod lang;
+
+pub mod io;
+use io::{IntcodeInput, IntcodeOutput};
+
+pub mod ascii;
+
+pub mod threaded;
+pub mod network;
+
+pub mod trace;
+use trace::{TraceSink, TraceEvent, TracedOperand, MemWrite};
+
+pub mod snapshot;
+use snapshot::Snapshot;
+
+pub mod icb;
+
+pub mod history;
+use history::History;
+
+mod cache;
+use cache::DecodeCache;
+
+mod cycle;
+use cycle::CycleDetector;
+
+pub mod watch;
+use watch::{Watches, WatchHit};
+
+pub mod profile;
+use profile::Profile;
+
+pub mod cfg;
+pub mod aot;
+pub mod gdb;
+
+/// the default word type, which is big enough for all the puzzles
 pub type Word = i64;
 
-#[derive(Debug)]
-pub enum ExecError {
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ExecError<W = Word> {
     InputBlocked,
+
+    /// the instruction at `pc` doesn't have a valid op in its lowest two digits
+    BadOpcode { pc: usize, word: W },
+
+    /// the instruction at `pc` has a mode digit other than 0, 1 or 2 for parameter `param`
+    BadMode { pc: usize, word: W, param: usize, mode: W },
+
+    /// the instruction at `pc` tried to read, write or jump to a negative address
+    NegativeAddress { pc: usize, word: W, addr: W },
+
+    /// the instruction at `pc` tried to read, write or jump to an address too big to exist
+    AddressTooLarge { pc: usize, word: W, addr: W },
+
+    /// the instruction at `pc` tried to use an address offset from its operand by the relative
+    /// base, or calculate a result, which doesn't fit in its word type
+    Overflow { pc: usize, word: W },
+
+    /// the instruction at `pc` uses immediate mode for parameter `param`, which it writes to
+    ImmediateWrite { pc: usize, word: W, param: usize },
+
+    /// the budget set with `Computer::set_step_budget` ran out before the instruction at the pc
+    StepBudgetExceeded,
+
+    /// the program can never leave a loop made of the instructions from `start` to `end`
+    InfiniteLoop { start: usize, end: usize },
 }
 
-pub type ExecResult<T> = Result<T, ExecError>;
+impl<W> ExecError<W> {
+    /// the address of the instruction that caused this error, if it was caused by a bad instruction
+    pub fn pc(&self) -> Option<usize> {
+        match self {
+            ExecError::InputBlocked
+            | ExecError::StepBudgetExceeded
+            | ExecError::InfiniteLoop { .. } => None,
+            ExecError::BadOpcode { pc, .. }
+            | ExecError::BadMode { pc, .. }
+            | ExecError::NegativeAddress { pc, .. }
+            | ExecError::AddressTooLarge { pc, .. }
+            | ExecError::Overflow { pc, .. }
+            | ExecError::ImmediateWrite { pc, .. } => Some(*pc),
+        }
+    }
+}
 
-fn as_addr(word: Word) -> usize {
-    match word.try_into() {
-        Ok(addr) => addr,
-        Err(..) => panic!("bad address: {}", word),
+impl<W: fmt::Display> fmt::Display for ExecError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecError::InputBlocked => write!(f, "blocked waiting for input"),
+            ExecError::BadOpcode { pc, word } => {
+                write!(f, "bad opcode at {}: {}", pc, word)
+            }
+            ExecError::BadMode { pc, word, param, mode } => {
+                write!(f, "bad mode digit for param {} in opcode {} at {}: {}", param, word, pc, mode)
+            }
+            ExecError::NegativeAddress { pc, word, addr } => {
+                write!(f, "negative address in opcode {} at {}: {}", word, pc, addr)
+            }
+            ExecError::AddressTooLarge { pc, word, addr } => {
+                write!(f, "address too large in opcode {} at {}: {}", word, pc, addr)
+            }
+            ExecError::Overflow { pc, word } => {
+                write!(f, "overflow in opcode {} at {}", word, pc)
+            }
+            ExecError::ImmediateWrite { pc, word, param } => {
+                write!(f, "immediate mode output param {} in opcode {} at {}", param, word, pc)
+            }
+            ExecError::StepBudgetExceeded => write!(f, "step budget exceeded"),
+            ExecError::InfiniteLoop { start, end } => {
+                write!(f, "stuck in infinite loop from {} to {}", start, end)
+            }
+        }
     }
 }
 
+impl<W: fmt::Debug + fmt::Display> Error for ExecError<W> {
+}
+
+pub type ExecResult<T, W = Word> = Result<T, ExecError<W>>;
+
+/// what happened when executing a single instruction
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
-enum Op {
+pub enum Step {
+    Executed,
+    OutputProduced,
+    Halted,
+}
+
+/// why `Computer::run_until` returned
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum StopReason<W = Word> {
+    Halted,
+    NeedsInput,
+
+    /// a value was written to the output, which is the end of `out_buf` unless the computer
+    /// was run with a different output
+    OutputProduced,
+
+    StepLimitReached,
+
+    /// execution reached a breakpoint at this address, which hasn't been executed yet
+    BreakpointHit(usize),
+
+    /// the last instruction executed triggered a watchpoint or code write detection that stops
+    WatchpointHit(WatchHit<W>),
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Op {
     Add,
     Mul,
     In,
@@ -34,245 +181,758 @@ enum Op {
     Off,
 }
 
+impl Op {
+    pub fn param_count(self) -> usize {
+        match self {
+            Op::Add | Op::Mul | Op::Lt | Op::Eq => 3,
+            Op::Jz | Op::Jnz => 2,
+            Op::In | Op::Out | Op::Off => 1,
+            Op::Hcf => 0,
+        }
+    }
+
+    /// the index of the parameter this op writes its result to, if it has one
+    pub fn out_param(self) -> Option<usize> {
+        match self {
+            Op::Add | Op::Mul | Op::Lt | Op::Eq => Some(2),
+            Op::In => Some(0),
+            Op::Out | Op::Jz | Op::Jnz | Op::Off | Op::Hcf => None,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Op::Add => "ADD",
+            Op::Mul => "MUL",
+            Op::In => "IN",
+            Op::Out => "OUT",
+            Op::Jnz => "JNZ",
+            Op::Jz => "JZ",
+            Op::Lt => "LT",
+            Op::Eq => "EQ",
+            Op::Off => "ARB",
+            Op::Hcf => "HALT",
+        }
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+        const OPS: [Op; 10] = [
+            Op::Add, Op::Mul, Op::In, Op::Out, Op::Jnz, Op::Jz, Op::Lt, Op::Eq, Op::Off, Op::Hcf,
+        ];
+
+        OPS.iter().cloned().find(|op| op.mnemonic().eq_ignore_ascii_case(mnemonic))
+    }
+
+    fn code(self) -> Word {
+        match self {
+            Op::Add => 1,
+            Op::Mul => 2,
+            Op::In => 3,
+            Op::Out => 4,
+            Op::Jnz => 5,
+            Op::Jz => 6,
+            Op::Lt => 7,
+            Op::Eq => 8,
+            Op::Off => 9,
+            Op::Hcf => 99,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
-enum Mode {
+pub enum Mode {
     Pointer,
     Immediate,
     Relative,
 }
 
-struct OpCode {
-    op: Op,
-    param_modes: Vec<Mode>,
+impl Mode {
+    fn digit(self) -> Word {
+        match self {
+            Mode::Pointer => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        }
+    }
 }
 
-impl From<Word> for OpCode {
-    fn from(word: Word) -> Self {
-        let mut digits = word.digits().rev();
-        let op = {
-            let lo = digits.next().unwrap();
+/// the op and parameter modes of an instruction word, and the raw values of its parameters if
+/// it was fetched from memory. ops have at most three parameters, and any parameters without a
+/// mode digit use pointer mode
+#[derive(Clone)]
+struct OpCode<W = Word> {
+    word: W,
+    op: Op,
+    param_modes: [Mode; 3],
+    params: [W; 3],
+}
 
-            let code = if let Some(hi) = digits.next() {
-                lo + hi * 10
-            } else {
-                lo
-            };
+impl<W: IntcodeWord> OpCode<W> {
+    fn decode(pc: usize, word: &W) -> ExecResult<Self, W> {
+        let bad_opcode = || ExecError::BadOpcode { pc, word: word.clone() };
+        let digits = match word.to_i64() {
+            Some(digits) if digits > 0 => digits,
+            _ => return Err(bad_opcode()),
+        };
 
-            match code {
-                1 => Op::Add,
-                2 => Op::Mul,
-                3 => Op::In,
-                4 => Op::Out,
-                5 => Op::Jnz,
-                6 => Op::Jz,
-                7 => Op::Lt,
-                8 => Op::Eq,
-                9 => Op::Off,
-                99 => Op::Hcf,
-                _ => panic!("bad op value in opcode {}: {}", word, code),
-            }
+        let op = match digits % 100 {
+            1 => Op::Add,
+            2 => Op::Mul,
+            3 => Op::In,
+            4 => Op::Out,
+            5 => Op::Jnz,
+            6 => Op::Jz,
+            7 => Op::Lt,
+            8 => Op::Eq,
+            9 => Op::Off,
+            99 => Op::Hcf,
+            _ => return Err(bad_opcode()),
         };
 
-        let mut param_modes = Vec::new();
-        while let Some(mode) = digits.next() {
-            param_modes.push(match mode {
+        let mut param_modes = [Mode::Pointer; 3];
+        let mut modes = digits / 100;
+        let mut param = 0;
+        while modes > 0 {
+            let mode = match modes % 10 {
                 0 => Mode::Pointer,
                 1 => Mode::Immediate,
                 2 => Mode::Relative,
-                _ => panic!("bad mode digit in opcode {}: {}", word, mode)
-            })
+                mode => return Err(ExecError::BadMode {
+                    pc,
+                    word: word.clone(),
+                    param,
+                    mode: W::from_i64(mode),
+                }),
+            };
+
+            if let Some(param_mode) = param_modes.get_mut(param) {
+                *param_mode = mode;
+            }
+
+            modes /= 10;
+            param += 1;
         }
 
-        Self {
+        Ok(Self {
+            word: word.clone(),
             op,
             param_modes,
+            params: [W::from_i64(0), W::from_i64(0), W::from_i64(0)],
+        })
+    }
+
+    /// decode the instruction at `pc` along with its parameters
+    fn fetch(mem: &Memory<W>, pc: usize) -> ExecResult<Self, W> {
+        let mut opcode = Self::decode(pc, &mem.load(pc))?;
+        opcode.load_params(mem, pc);
+        Ok(opcode)
+    }
+
+    /// read the parameters of this instruction at `pc`
+    fn load_params(&mut self, mem: &Memory<W>, pc: usize) {
+        for param in 0..self.op.param_count() {
+            self.params[param] = mem.load(pc + 1 + param);
         }
     }
+
+    fn param_mode(&self, param: usize) -> Mode {
+        self.param_modes[param]
+    }
 }
 
 impl OpCode {
-    fn param_mode(&self, param: usize) -> Mode {
-        self.param_modes.get(param).cloned().unwrap_or(Mode::Pointer)
+    fn encode(op: Op, param_modes: &[Mode]) -> Word {
+        param_modes.iter().rev()
+            .fold(0, |modes, mode| modes * 10 + mode.digit()) * 100 + op.code()
     }
 }
 
+/// parse a program, ignoring whitespace. panics if it isn't valid, so use `load::parse` for
+/// programs which might not be
 pub fn from_str(input: &str) -> Vec<Word> {
-    input.split(",").map(|int| int.parse().unwrap()).collect()
+    from_str_as(input)
 }
 
-pub struct Computer {
-    pub in_buf: Vec<Word>,
-    pub out_buf: Vec<Word>,
+/// parse a program for a computer with a different word type
+pub fn from_str_as<W: IntcodeWord>(input: &str) -> Vec<W> {
+    load::parse_as(input, ParseMode::Tolerant).unwrap_or_else(|err| panic!("bad program: {}", err))
+}
 
-    mem: Vec<Word>,
+pub struct Computer<W = Word> {
+    pub in_buf: VecDeque<W>,
+    pub out_buf: VecDeque<W>,
+
+    mem: Memory<W>,
 
     pc: usize,
-    rel_offset: Word,
+    rel_offset: W,
+
+    breakpoints: HashSet<usize>,
+    /// the pc of the last `BreakpointHit`, which the next run steps past instead of stopping
+    breakpoint_hit: Option<usize>,
+    watches: Watches<W>,
+
+    decoded: Option<DecodeCache<W>>,
+
+    step_budget: Option<u64>,
+    cycles: Option<CycleDetector<W>>,
+
+    tracer: Option<Box<dyn TraceSink<W>>>,
+    history: Option<History<W>>,
+    profile: Option<Profile>,
+    trace_event: Option<TraceEvent<W>>,
 }
 
-impl Computer {
-    pub fn new(code: Vec<Word>) -> Self {
+impl<W: IntcodeWord> Computer<W> {
+    pub fn new(code: Vec<W>) -> Self {
         Self {
-            in_buf: Vec::new(),
-            out_buf: Vec::new(),
+            in_buf: VecDeque::new(),
+            out_buf: VecDeque::new(),
 
-            mem: code,
+            mem: Memory::from(code),
 
             pc: 0,
-            rel_offset: 0,
+            rel_offset: W::from_i64(0),
+
+            breakpoints: HashSet::new(),
+            breakpoint_hit: None,
+            watches: Watches::new(),
+
+            decoded: Some(DecodeCache::new()),
+
+            step_budget: None,
+            cycles: None,
+
+            tracer: None,
+            history: None,
+            profile: None,
+            trace_event: None,
         }
     }
 
-    fn load(&mut self, mode: Mode, val: Word) -> Word {
-        match mode {
-            Mode::Pointer => self.mem_load(as_addr(val)),
-            Mode::Immediate => val,
-            Mode::Relative => self.mem_load(as_addr(val + self.rel_offset)),
+    pub fn from_snapshot(snapshot: &Snapshot<W>) -> Self {
+        let mut computer = Self::new(Vec::new());
+        computer.restore(snapshot);
+        computer
+    }
+
+    /// save the memory, registers and i/o buffers. breakpoints and tracing aren't included
+    pub fn snapshot(&self) -> Snapshot<W> {
+        Snapshot {
+            mem: self.mem.clone(),
+            pc: self.pc,
+            rel_offset: self.rel_offset.clone(),
+            in_buf: self.in_buf.clone(),
+            out_buf: self.out_buf.iter().cloned().collect(),
         }
     }
 
-    pub fn mem_load(&mut self, addr: usize) -> Word {
-        self.mem.get(addr).cloned().unwrap_or(0)
+    /// restore a saved state. if the computer is recording, its history is discarded
+    pub fn restore(&mut self, snapshot: &Snapshot<W>) {
+        if let Some(history) = &self.history {
+            self.history = Some(History::new(history.limit()));
+        }
+
+        self.mem = snapshot.mem.clone();
+        if let Some(decoded) = &mut self.decoded {
+            decoded.revalidate(&snapshot.mem);
+        }
+        if self.cycles.is_some() {
+            self.cycles = Some(CycleDetector::new(&self.mem));
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.restarted();
+        }
+        self.watches.reset_executed();
+
+        self.pc = snapshot.pc;
+        self.breakpoint_hit = None;
+        self.rel_offset = snapshot.rel_offset.clone();
+        self.in_buf = snapshot.in_buf.clone();
+        self.out_buf = snapshot.out_buf.iter().cloned().collect();
     }
 
-    pub fn mem_store(&mut self, addr: usize, val: Word) {
-        // todo: pages
-        while self.mem.len() < addr {
-            self.mem.resize(self.mem.len() * 2, 0);
+    /// instructions which are executed more than once are decoded once and cached by address,
+    /// which is on by default. the cache can be turned off to compare performance
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decoded = if enabled { Some(DecodeCache::new()) } else { None };
+    }
+
+    /// limit the number of instructions that can be executed from now on, across any number of
+    /// runs. once the budget is used up, execution stops with `ExecError::StepBudgetExceeded`
+    pub fn set_step_budget(&mut self, budget: Option<u64>) {
+        self.step_budget = budget;
+    }
+
+    /// the number of instructions left in the step budget
+    pub fn step_budget(&self) -> Option<u64> {
+        self.step_budget
+    }
+
+    /// record every instruction executed from now on with `tracer`, or stop tracing if it's `None`
+    pub fn set_tracer(&mut self, tracer: Option<Box<dyn TraceSink<W>>>) {
+        self.tracer = tracer;
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Box<dyn TraceSink<W>>> {
+        self.tracer.take()
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn rel_offset(&self) -> W {
+        self.rel_offset.clone()
+    }
+
+    pub fn has_breakpoint(&self, addr: usize) -> bool {
+        self.breakpoints.contains(&addr)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item=usize> + '_ {
+        self.breakpoints.iter().cloned()
+    }
+
+    pub fn add_breakpoint(&mut self, addr: usize) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    fn addr(&self, opcode: &OpCode<W>, addr: W) -> ExecResult<usize, W> {
+        match addr.to_i64().and_then(|addr| usize::try_from(addr).ok()) {
+            Some(addr) => Ok(addr),
+            None if addr < W::from_i64(0) => Err(ExecError::NegativeAddress {
+                pc: self.pc,
+                word: opcode.word.clone(),
+                addr,
+            }),
+            None => Err(ExecError::AddressTooLarge {
+                pc: self.pc,
+                word: opcode.word.clone(),
+                addr,
+            }),
+        }
+    }
+
+    fn overflow(&self, opcode: &OpCode<W>) -> ExecError<W> {
+        ExecError::Overflow { pc: self.pc, word: opcode.word.clone() }
+    }
+
+    /// the address of a relative mode operand
+    fn rel_addr(&self, opcode: &OpCode<W>, val: &W) -> ExecResult<usize, W> {
+        let addr = val.add(&self.rel_offset).ok_or_else(|| self.overflow(opcode))?;
+        self.addr(opcode, addr)
+    }
+
+    fn load(&mut self, opcode: &OpCode<W>, param: usize) -> ExecResult<W, W> {
+        let val = &opcode.params[param];
+        let mode = opcode.param_mode(param);
+
+        let loaded = match mode {
+            Mode::Pointer => self.load_watched(self.addr(opcode, val.clone())?),
+            Mode::Immediate => val.clone(),
+            Mode::Relative => self.load_watched(self.rel_addr(opcode, val)?),
+        };
+
+        if let Some(event) = &mut self.trace_event {
+            event.operands.push(TracedOperand { mode, raw: val.clone(), value: loaded.clone() });
+        }
+
+        Ok(loaded)
+    }
+
+    fn load_watched(&mut self, addr: usize) -> W {
+        let val = self.mem_load(addr);
+        if !self.watches.is_empty() {
+            self.watches.read(self.pc, addr, &val);
         }
+        val
+    }
 
-        self.mem[addr] = val;
+    /// write to memory as the current instruction, which can trigger watchpoints
+    fn store(&mut self, addr: usize, val: W) {
+        if !self.watches.is_empty() {
+            self.watches.write(self.pc, addr, &self.mem.load(addr), &val);
+        }
+        self.mem_store(addr, val);
     }
 
-    fn get_ptr(&self, mode: Mode, val: Word) -> usize {
-        as_addr(match mode {
-            Mode::Pointer => val,
-            Mode::Relative => val + self.rel_offset,
-            Mode::Immediate => panic!("in instruction doesn't allow immediate arg"),
+    pub fn mem_load(&self, addr: usize) -> W {
+        self.mem.load(addr)
+    }
+
+    pub fn mem_store(&mut self, addr: usize, val: W) {
+        if let Some(event) = &mut self.trace_event {
+            event.writes.push(MemWrite { addr, old: self.mem.load(addr), new: val.clone() });
+        }
+        if let Some(cycles) = &mut self.cycles {
+            cycles.write(addr, &self.mem.load(addr), &val);
+        }
+
+        self.mem.store(addr, val);
+
+        // the program might be modifying its own code
+        if let Some(decoded) = &mut self.decoded {
+            decoded.write(addr);
+        }
+    }
+
+    pub fn memory(&self) -> &Memory<W> {
+        &self.mem
+    }
+
+    fn get_ptr(&mut self, opcode: &OpCode<W>, param: usize) -> ExecResult<usize, W> {
+        let val = &opcode.params[param];
+        let mode = opcode.param_mode(param);
+
+        let addr = match mode {
+            Mode::Pointer => self.addr(opcode, val.clone())?,
+            Mode::Relative => self.rel_addr(opcode, val)?,
+            Mode::Immediate => return Err(ExecError::ImmediateWrite {
+                pc: self.pc,
+                word: opcode.word.clone(),
+                param,
+            }),
+        };
+
+        if let Some(event) = &mut self.trace_event {
+            let value = W::from_i64(addr as i64);
+            event.operands.push(TracedOperand { mode, raw: val.clone(), value });
+        }
+
+        Ok(addr)
+    }
+
+    pub fn run(&mut self) -> ExecResult<(), W> {
+        self.with_bufs(|computer, in_buf, out_buf| computer.run_with(in_buf, out_buf))
+    }
+
+    /// run until the program halts, blocks on input, produces an output or reaches a breakpoint,
+    /// or until `step_limit` instructions have been executed. a breakpoint at the current pc
+    /// is ignored so that execution can be resumed after stopping at it
+    pub fn run_until(&mut self, step_limit: Option<u64>) -> ExecResult<StopReason<W>, W> {
+        self.with_bufs(|computer, in_buf, out_buf| {
+            computer.run_until_with(in_buf, out_buf, step_limit)
         })
     }
 
-    pub fn run(&mut self) -> ExecResult<()> {
+    /// execute the single instruction at the current pc
+    pub fn step(&mut self) -> ExecResult<Step, W> {
+        self.with_bufs(|computer, in_buf, out_buf| computer.step_with(in_buf, out_buf))
+    }
+
+    fn with_bufs<T, F>(&mut self, f: F) -> T
+        where F: FnOnce(&mut Self, &mut VecDeque<W>, &mut VecDeque<W>) -> T
+    {
+        let mut in_buf = mem::take(&mut self.in_buf);
+        let mut out_buf = mem::take(&mut self.out_buf);
+
+        let result = f(self, &mut in_buf, &mut out_buf);
+
+        self.in_buf = in_buf;
+        self.out_buf = out_buf;
+        result
+    }
+
+    /// like `run`, but reading input from and writing output to the given endpoints instead of
+    /// `in_buf` and `out_buf`
+    pub fn run_with<I, O>(&mut self, input: &mut I, output: &mut O) -> ExecResult<(), W>
+        where I: IntcodeInput<W> + ?Sized, O: IntcodeOutput<W> + ?Sized
+    {
         loop {
-            let opcode = OpCode::from(self.mem[self.pc]);
+            if let Step::Halted = self.step_with(input, output)? {
+                break Ok(());
+            }
+        }
+    }
 
-            match opcode.op {
-                Op::Add => {
-                    let a_pos = self.mem_load(self.pc + 1);
-                    let a = self.load(opcode.param_mode(0), a_pos);
+    pub fn run_until_with<I, O>(
+        &mut self,
+        input: &mut I,
+        output: &mut O,
+        step_limit: Option<u64>
+    ) -> ExecResult<StopReason<W>, W>
+        where I: IntcodeInput<W> + ?Sized, O: IntcodeOutput<W> + ?Sized
+    {
+        let mut steps = 0;
+        let resumed_from = self.breakpoint_hit.take();
 
-                    let b_pos = self.mem_load(self.pc + 2);
-                    let b = self.load(opcode.param_mode(1), b_pos);
+        loop {
+            let resuming = steps == 0 && resumed_from == Some(self.pc);
+            if !resuming && self.breakpoints.contains(&self.pc) {
+                self.breakpoint_hit = Some(self.pc);
+                break Ok(StopReason::BreakpointHit(self.pc));
+            }
 
-                    let out = self.mem_load(self.pc + 3);
-                    let out_pos = self.get_ptr(opcode.param_mode(2), out);
+            if step_limit.map(|limit| steps >= limit).unwrap_or(false) {
+                break Ok(StopReason::StepLimitReached);
+            }
 
-                    self.mem_store(out_pos, a + b);
-                    self.pc += 4;
-                }
+            let step = match self.step_with(input, output) {
+                Ok(step) => step,
+                Err(ExecError::InputBlocked) => break Ok(StopReason::NeedsInput),
+                Err(err) => break Err(err),
+            };
+            steps += 1;
 
-                Op::Mul => {
-                    let a_pos = self.mem_load(self.pc + 1);
-                    let a = self.load(opcode.param_mode(0), a_pos);
+            if let Some(hit) = self.watches.stop.take() {
+                break Ok(StopReason::WatchpointHit(hit));
+            }
 
-                    let b_pos = self.mem_load(self.pc + 2);
-                    let b = self.load(opcode.param_mode(1), b_pos);
+            match step {
+                Step::Executed => continue,
+                Step::OutputProduced => break Ok(StopReason::OutputProduced),
+                Step::Halted => break Ok(StopReason::Halted),
+            }
+        }
+    }
+
+    pub fn step_with<I, O>(&mut self, input: &mut I, output: &mut O) -> ExecResult<Step, W>
+        where I: IntcodeInput<W> + ?Sized, O: IntcodeOutput<W> + ?Sized
+    {
+        if self.step_budget == Some(0) {
+            return Err(ExecError::StepBudgetExceeded);
+        }
+
+        let opcode = match &mut self.decoded {
+            Some(decoded) => decoded.fetch(&self.mem, self.pc)?,
+            None => OpCode::fetch(&self.mem, self.pc)?,
+        };
+
+        let pc = self.pc;
+        let rel_offset = self.rel_offset.clone();
+        self.breakpoint_hit = None;
+        self.watches.stop = None;
+        self.watches.executing(pc, opcode.op.param_count() + 1);
+        self.trace_event = if self.tracer.is_some() || self.history.is_some() || self.profile.is_some() {
+            Some(TraceEvent::new(self.pc, opcode.op))
+        } else {
+            None
+        };
+
+        let step = match opcode.op {
+            Op::Add => {
+                let a = self.load(&opcode, 0)?;
+                let b = self.load(&opcode, 1)?;
+                let out_pos = self.get_ptr(&opcode, 2)?;
+
+                let sum = a.add(&b).ok_or_else(|| self.overflow(&opcode))?;
+                self.store(out_pos, sum);
+                self.pc += 4;
+
+                Step::Executed
+            }
+
+            Op::Mul => {
+                let a = self.load(&opcode, 0)?;
+                let b = self.load(&opcode, 1)?;
+                let out_pos = self.get_ptr(&opcode, 2)?;
+
+                let product = a.mul(&b).ok_or_else(|| self.overflow(&opcode))?;
+                self.store(out_pos, product);
+                self.pc += 4;
+
+                Step::Executed
+            }
 
-                    let out = self.mem_load(self.pc + 3);
-                    let out_pos = self.get_ptr(opcode.param_mode(2), out);
+            Op::Jnz => {
+                let a = self.load(&opcode, 0)?;
+                let b = self.load(&opcode, 1)?;
 
-                    self.mem_store(out_pos, a * b);
-                    self.pc += 4;
+                if !a.is_zero() {
+                    self.pc = self.addr(&opcode, b)?;
+                } else {
+                    self.pc += 3;
                 }
 
-                Op::Jnz => {
-                    let a_pos = self.mem_load(self.pc + 1);
-                    let a = self.load(opcode.param_mode(0), a_pos);
+                Step::Executed
+            }
 
-                    let b_pos = self.mem_load(self.pc + 2);
-                    let b = self.load(opcode.param_mode(1), b_pos);
+            Op::Jz => {
+                let a = self.load(&opcode, 0)?;
+                let b = self.load(&opcode, 1)?;
 
-                    if a != 0 {
-                        self.pc = as_addr(b);
-                    } else {
-                        self.pc += 3;
-                    }
+                if a.is_zero() {
+                    self.pc = self.addr(&opcode, b)?;
+                } else {
+                    self.pc += 3;
                 }
 
-                Op::Jz => {
-                    let a_pos = self.mem_load(self.pc + 1);
-                    let a = self.load(opcode.param_mode(0), a_pos);
+                Step::Executed
+            }
 
-                    let b_pos = self.mem_load(self.pc + 2);
-                    let b = self.load(opcode.param_mode(1), b_pos);
+            Op::In => {
+                let at_pos = self.get_ptr(&opcode, 0)?;
 
-                    if a == 0 {
-                        self.pc = as_addr(b);
-                    } else {
-                        self.pc += 3;
-                    }
+                let in_val = input.read().ok_or(ExecError::InputBlocked)?;
+                if let Some(event) = &mut self.trace_event {
+                    event.input = Some(in_val.clone());
+                }
+                self.store(at_pos, in_val);
+                if let Some(cycles) = &mut self.cycles {
+                    cycles.input_read();
                 }
 
-                Op::In => {
-                    if self.in_buf.is_empty() {
-                        return Err(ExecError::InputBlocked);
-                    }
+                self.pc += 2;
 
-                    let at = self.mem_load(self.pc + 1);
-                    let at_pos = self.get_ptr(opcode.param_mode(0), at);
+                Step::Executed
+            }
 
-                    let in_val = self.in_buf.remove(0);
-                    self.mem_store(at_pos, in_val);
+            Op::Out => {
+                let val = self.load(&opcode, 0)?;
 
-                    self.pc += 2;
+                if let Some(event) = &mut self.trace_event {
+                    event.output = Some(val.clone());
                 }
+                output.write(val);
+                self.pc += 2;
 
-                Op::Out => {
-                    let val_pos = self.mem_load(self.pc + 1);
-                    let val = self.load(opcode.param_mode(0), val_pos);
+                Step::OutputProduced
+            }
 
-                    self.out_buf.push(val);
-                    self.pc += 2;
-                }
+            Op::Lt => {
+                let a = self.load(&opcode, 0)?;
+                let b = self.load(&opcode, 1)?;
+                let out_pos = self.get_ptr(&opcode, 2)?;
+
+                self.store(out_pos, W::from_i64(if a < b { 1 } else { 0 }));
+                self.pc += 4;
 
-                Op::Lt => {
-                    let a_pos = self.mem_load(self.pc + 1);
-                    let a = self.load(opcode.param_mode(0), a_pos);
+                Step::Executed
+            }
 
-                    let b_pos = self.mem_load(self.pc + 2);
-                    let b = self.load(opcode.param_mode(1), b_pos);
+            Op::Eq => {
+                let a = self.load(&opcode, 0)?;
+                let b = self.load(&opcode, 1)?;
+                let out_pos = self.get_ptr(&opcode, 2)?;
 
-                    let out = self.mem_load(self.pc + 3);
-                    let out_pos = self.get_ptr(opcode.param_mode(2), out);
+                self.store(out_pos, W::from_i64(if a == b { 1 } else { 0 }));
+                self.pc += 4;
 
-                    self.mem_store(out_pos, if a < b { 1 } else { 0 });
-                    self.pc += 4;
-                }
+                Step::Executed
+            }
 
-                Op::Eq => {
-                    let a_pos = self.mem_load(self.pc + 1);
-                    let a = self.load(opcode.param_mode(0), a_pos);
+            Op::Off => {
+                let a = self.load(&opcode, 0)?;
 
-                    let b_pos = self.mem_load(self.pc + 2);
-                    let b = self.load(opcode.param_mode(1), b_pos);
+                self.rel_offset = self.rel_offset.add(&a).ok_or_else(|| self.overflow(&opcode))?;
+                self.pc += 2;
 
-                    let out = self.mem_load(self.pc + 3);
-                    let out_pos = self.get_ptr(opcode.param_mode(2), out);
+                Step::Executed
+            }
 
-                    self.mem_store(out_pos, if a == b { 1 } else { 0 });
-                    self.pc += 4;
-                }
+            Op::Hcf => Step::Halted,
+        };
 
-                Op::Off => {
-                    let a_pos = self.mem_load(self.pc + 1);
-                    let a = self.load(opcode.param_mode(0), a_pos);
+        if let Some(mut event) = self.trace_event.take() {
+            event.rel_offset = self.rel_offset.clone();
 
-                    self.rel_offset += a;
-                    self.pc += 2;
-                }
+            if let Some(tracer) = &mut self.tracer {
+                tracer.record(&event);
+            }
+            if let Some(profile) = &mut self.profile {
+                profile.record(&rel_offset, &event);
+            }
+            if let Some(history) = &mut self.history {
+                history.push(rel_offset, event);
+            }
+        }
 
-                Op::Hcf => break Ok(()),
+        if let Some(budget) = &mut self.step_budget {
+            *budget -= 1;
+        }
+
+        if let Some(cycles) = &mut self.cycles {
+            let jumped = opcode.op == Op::Jnz || opcode.op == Op::Jz;
+            if let Some((start, end)) = cycles.step(pc, jumped, self.pc, &self.rel_offset, &self.mem) {
+                return Err(ExecError::InfiniteLoop { start, end });
             }
         }
+
+        Ok(step)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run_program(code: &str) -> (Computer, ExecResult<()>) {
+        let mut computer = Computer::new(from_str(code));
+        let result = computer.run();
+        (computer, result)
+    }
+
+    #[test]
+    fn bad_opcode_is_reported() {
+        let (computer, result) = run_program("1101,1,1,5,42,99");
+        assert_eq!(result, Err(ExecError::BadOpcode { pc: 4, word: 42 }));
+        assert_eq!(computer.pc, 4);
+    }
+
+    #[test]
+    fn bad_mode_is_reported() {
+        let (_, result) = run_program("3101,1,1,5,99");
+        assert_eq!(result, Err(ExecError::BadMode { pc: 0, word: 3101, param: 1, mode: 3 }));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn negative_address_is_reported() {
+        let (_, result) = run_program("4,-3,99");
+        assert_eq!(result, Err(ExecError::NegativeAddress { pc: 0, word: 4, addr: -3 }));
+
+        let (_, result) = run_program("1105,1,-1");
+        assert_eq!(result, Err(ExecError::NegativeAddress { pc: 0, word: 1105, addr: -1 }));
+    }
+
+    #[test]
+    fn immediate_write_is_reported() {
+        let (computer, result) = run_program("1101,1,1,5,11101,1,1,5,99");
+        assert_eq!(result, Err(ExecError::ImmediateWrite { pc: 4, word: 11101, param: 2 }));
+        assert_eq!(computer.mem_load(5), 2);
+    }
+
+    #[test]
+    fn run_until_stops_at_events() {
+        let mut computer = Computer::new(from_str("3,11,4,11,1101,1,1,12,4,12,99,0,0"));
+        computer.add_breakpoint(8);
+
+        assert_eq!(computer.run_until(None), Ok(StopReason::NeedsInput));
+        computer.in_buf.push_back(7);
+        assert_eq!(computer.run_until(None), Ok(StopReason::OutputProduced));
+        assert_eq!(computer.out_buf, [7]);
+        assert_eq!(computer.run_until(None), Ok(StopReason::BreakpointHit(8)));
+        assert_eq!(computer.run_until(Some(1)), Ok(StopReason::OutputProduced));
+        assert_eq!(computer.out_buf, [7, 2]);
+        assert_eq!(computer.run_until(Some(0)), Ok(StopReason::StepLimitReached));
+        assert_eq!(computer.run_until(None), Ok(StopReason::Halted));
+        assert_eq!(computer.step(), Ok(Step::Halted));
+    }
+
+    #[test]
+    fn run_until_stops_at_breakpoints_after_other_stops() {
+        let mut computer = Computer::new(from_str("104,1,104,2,99"));
+        computer.add_breakpoint(2);
+
+        assert_eq!(computer.run_until(None), Ok(StopReason::OutputProduced));
+        assert_eq!(computer.run_until(None), Ok(StopReason::BreakpointHit(2)));
+        assert_eq!(computer.run_until(None), Ok(StopReason::OutputProduced));
+        assert_eq!(computer.run_until(None), Ok(StopReason::Halted));
+    }
+
+    #[test]
+    fn store_past_end_of_program() {
+        let (computer, result) = run_program("1101,1,2,5,99");
+        assert_eq!(result, Ok(()));
+        assert_eq!(computer.mem_load(5), 3);
+
+        let (computer, result) = run_program("21101,3,4,1000000000,99");
+        assert_eq!(result, Ok(()));
+        assert_eq!(computer.mem_load(1_000_000_000), 7);
+        assert_eq!(computer.memory().page_count(), 2);
+    }
+}
@@ -0,0 +1,476 @@
+//! ahead-of-time translation of intcode programs to rust source. `transpile` emits a function
+//! which runs a computer loaded with the program, using a `match` on the pc with an arm for each
+//! instruction that can be found by following the program's control flow from address 0.
+//! immediate operands become constants, and are folded where all of an instruction's inputs
+//! are immediate.
+//!
+//! the generated function behaves the same as `Computer::run`: it returns `InputBlocked` with
+//! the computer stopped at the input instruction, and can be called again to resume. if the
+//! program jumps somewhere that wasn't compiled, writes over one of its compiled instructions,
+//! or causes an error, the rest of the run is handed to the interpreter. the function refers to
+//! the `intcode` module as `crate::intcode`
+
+use super::{Word, Op, Mode, Computer, ExecError, ExecResult};
+use super::disasm::Instruction;
+use super::word::IntcodeWord;
+use super::cfg::find_instructions;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+/// the state of a computer running a transpiled program
+pub struct Vm<'a> {
+    computer: &'a mut Computer,
+
+    /// the words of the compiled instructions, as sorted ranges
+    covered: &'a [Range<usize>],
+}
+
+impl<'a> Vm<'a> {
+    /// whether the compiled instructions in `code` are all unchanged in `computer`'s memory.
+    /// computers that are tracing, recording history, profiling, watching memory, limiting steps
+    /// or detecting loops always need the interpreter
+    pub fn can_run(computer: &Computer, code: &[Word], covered: &[Range<usize>]) -> bool {
+        computer.tracer.is_none()
+            && computer.history.is_none()
+            && computer.profile.is_none()
+            && computer.watches.is_empty()
+            && computer.step_budget.is_none()
+            && computer.cycles.is_none()
+            && covered.iter()
+                .flat_map(|range| range.clone())
+                .all(|addr| computer.mem_load(addr) == code[addr])
+    }
+
+    pub fn new(computer: &'a mut Computer, covered: &'a [Range<usize>]) -> Self {
+        Self {
+            computer,
+            covered,
+        }
+    }
+
+    pub fn pc(&self) -> usize {
+        self.computer.pc
+    }
+
+    pub fn goto(&mut self, pc: usize) {
+        self.computer.pc = pc;
+    }
+
+    /// jump to a computed address, returning false if it's negative
+    pub fn jump(&mut self, target: Word) -> bool {
+        match target {
+            target if target >= 0 => {
+                self.computer.pc = target as usize;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn load(&self, addr: usize) -> Word {
+        self.computer.mem_load(addr)
+    }
+
+    /// the address of a relative operand, or `None` if it's negative or the interpreter would
+    /// fail to calculate it
+    pub fn rel_addr(&self, offset: Word) -> Option<usize> {
+        match offset.add(&self.computer.rel_offset) {
+            Some(addr) if addr >= 0 => Some(addr as usize),
+            _ => None,
+        }
+    }
+
+    /// returns true if this changed one of the compiled instructions, which means the rest of the
+    /// program has to be interpreted
+    pub fn store(&mut self, addr: usize, val: Word) -> bool {
+        let modified = self.is_compiled(addr) && self.computer.mem_load(addr) != val;
+        self.computer.mem_store(addr, val);
+        modified
+    }
+
+    pub fn input(&mut self) -> ExecResult<Word> {
+        self.computer.in_buf.pop_front().ok_or(ExecError::InputBlocked)
+    }
+
+    pub fn output(&mut self, val: Word) {
+        self.computer.out_buf.push_back(val);
+    }
+
+    /// adjust the relative base, returning false if the interpreter would fail to
+    pub fn offset(&mut self, val: Word) -> bool {
+        match self.computer.rel_offset.add(&val) {
+            Some(rel_offset) => {
+                self.computer.rel_offset = rel_offset;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// run the rest of the program in the interpreter, from the current pc
+    pub fn interpret(self) -> ExecResult<()> {
+        self.computer.run()
+    }
+
+    fn is_compiled(&self, addr: usize) -> bool {
+        self.covered
+            .binary_search_by(|range| {
+                if range.end <= addr {
+                    Ordering::Less
+                } else if range.start > addr {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+}
+
+/// merge the spans of `instructions` into sorted ranges
+fn covered_ranges(instructions: &BTreeMap<usize, Instruction>) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+
+    for (addr, instruction) in instructions {
+        let end = addr + instruction.size();
+        match ranges.last_mut() {
+            Some(last) if last.end == *addr => last.end = end,
+            _ => ranges.push(*addr..end),
+        }
+    }
+
+    ranges
+}
+
+enum Value {
+    Const(Word),
+    Var(&'static str),
+}
+
+impl Value {
+    fn text(&self) -> String {
+        match self {
+            Value::Const(Word::MIN) => "crate::intcode::Word::MIN".to_string(),
+            Value::Const(val) => val.to_string(),
+            Value::Var(name) => name.to_string(),
+        }
+    }
+}
+
+/// the body of one arm of the generated match, for the instruction at `pc`
+struct Arm<'a> {
+    pc: usize,
+    instruction: &'a Instruction,
+    covered: &'a [Range<usize>],
+    lines: Vec<String>,
+}
+
+const PARAM_NAMES: [&str; 3] = ["a", "b", "c"];
+
+impl<'a> Arm<'a> {
+    /// the value of parameter `param`, binding it to a variable unless it's immediate. `None`
+    /// means reading it always fails
+    fn read(&mut self, param: usize) -> Option<Value> {
+        let operand = self.instruction.operands[param];
+        let name = PARAM_NAMES[param];
+
+        match operand.mode {
+            Mode::Immediate => return Some(Value::Const(operand.value)),
+            Mode::Pointer if operand.value < 0 => return None,
+            Mode::Pointer => {
+                self.lines.push(format!("let {} = vm.load({});", name, operand.value));
+            }
+            Mode::Relative => self.lines.push(format!(
+                "let {} = match vm.rel_addr({}) {{ Some(addr) => vm.load(addr), None => return vm.interpret() }};",
+                name,
+                operand.value
+            )),
+        }
+
+        Some(Value::Var(name))
+    }
+
+    /// the address written by parameter `param`, and whether it might be part of a compiled
+    /// instruction. `None` means writing to it always fails
+    fn target(&mut self, param: usize) -> Option<(Value, bool)> {
+        let operand = self.instruction.operands[param];
+
+        match operand.mode {
+            Mode::Immediate => None,
+            Mode::Pointer if operand.value < 0 => None,
+            Mode::Pointer => {
+                let addr = operand.value as usize;
+                let compiled = self.covered.iter().any(|range| range.contains(&addr));
+                Some((Value::Const(operand.value), compiled))
+            }
+            Mode::Relative => {
+                self.lines.push(format!(
+                    "let out = match vm.rel_addr({}) {{ Some(addr) => addr, None => return vm.interpret() }};",
+                    operand.value
+                ));
+                Some((Value::Var("out"), true))
+            }
+        }
+    }
+
+    fn store(&mut self, target: (Value, bool), val: &str) {
+        let next = self.pc + self.instruction.size();
+        match target {
+            (addr, false) => {
+                self.lines.push(format!("vm.store({}, {});", addr.text(), val));
+                self.lines.push(format!("vm.goto({});", next));
+            }
+            (addr, true) => {
+                self.lines.push(format!("let modified = vm.store({}, {});", addr.text(), val));
+                self.lines.push(format!("vm.goto({});", next));
+                self.lines.push("if modified { return vm.interpret(); }".to_string());
+            }
+        }
+    }
+
+    fn jump(&self, target: &Value) -> String {
+        match target {
+            Value::Const(target) if *target >= 0 => format!("vm.goto({});", target),
+            Value::Const(_) => "return vm.interpret();".to_string(),
+            Value::Var(target) => format!("if !vm.jump({}) {{ return vm.interpret(); }}", target),
+        }
+    }
+
+    /// generate the lines of the arm, or `None` if the instruction always fails
+    fn generate(&mut self) -> Option<()> {
+        let next = self.pc + self.instruction.size();
+
+        match self.instruction.op {
+            op @ Op::Add | op @ Op::Mul | op @ Op::Lt | op @ Op::Eq => {
+                let a = self.read(0)?;
+                let b = self.read(1)?;
+                let target = self.target(2)?;
+
+                let val = match (op, &a, &b) {
+                    (Op::Add, Value::Const(a), Value::Const(b)) => Some(Value::Const(a.wrapping_add(*b))),
+                    (Op::Mul, Value::Const(a), Value::Const(b)) => Some(Value::Const(a.wrapping_mul(*b))),
+                    (Op::Lt, Value::Const(a), Value::Const(b)) => Some(Value::Const((a < b) as Word)),
+                    (Op::Eq, Value::Const(a), Value::Const(b)) => Some(Value::Const((a == b) as Word)),
+                    _ => None,
+                };
+
+                let val = match (op, val) {
+                    (_, Some(val)) => val.text(),
+                    // wrapping like the interpreter, rather than panicking in debug builds
+                    (Op::Add, None) => format!("crate::intcode::Word::wrapping_add({}, {})", a.text(), b.text()),
+                    (Op::Mul, None) => format!("crate::intcode::Word::wrapping_mul({}, {})", a.text(), b.text()),
+                    (Op::Lt, None) => format!("if {} < {} {{ 1 }} else {{ 0 }}", a.text(), b.text()),
+                    (_, None) => format!("if {} == {} {{ 1 }} else {{ 0 }}", a.text(), b.text()),
+                };
+                self.store(target, &val);
+            }
+
+            Op::In => {
+                let target = self.target(0)?;
+                self.lines.push("let input = vm.input()?;".to_string());
+                self.store(target, "input");
+            }
+
+            Op::Out => {
+                let a = self.read(0)?;
+                self.lines.push(format!("vm.output({});", a.text()));
+                self.lines.push(format!("vm.goto({});", next));
+            }
+
+            op @ Op::Jnz | op @ Op::Jz => {
+                let cond = self.read(0)?;
+                let target = self.read(1)?;
+                let jump = self.jump(&target);
+                let cmp = if op == Op::Jnz { "!=" } else { "==" };
+
+                match cond {
+                    Value::Const(cond) if (cond != 0) == (op == Op::Jnz) => self.lines.push(jump),
+                    Value::Const(_) => self.lines.push(format!("vm.goto({});", next)),
+                    cond => self.lines.push(format!(
+                        "if {} {} 0 {{ {} }} else {{ vm.goto({}); }}",
+                        cond.text(),
+                        cmp,
+                        jump,
+                        next
+                    )),
+                }
+            }
+
+            Op::Off => {
+                let a = self.read(0)?;
+                self.lines.push(format!("if !vm.offset({}) {{ return vm.interpret(); }}", a.text()));
+                self.lines.push(format!("vm.goto({});", next));
+            }
+
+            Op::Hcf => self.lines.push("return Ok(());".to_string()),
+        }
+
+        Some(())
+    }
+}
+
+/// rust source for a function called `name` which runs `code`. see the module docs
+pub fn transpile(code: &[Word], name: &str) -> String {
+    let (instructions, _) = find_instructions(code);
+    let covered = covered_ranges(&instructions);
+
+    let mut src = String::new();
+    src.push_str(&format!("/// transpiled from an intcode program of {} words\n", code.len()));
+    src.push_str("#[allow(unreachable_code, clippy::all)]\n");
+    src.push_str(&format!(
+        "pub fn {}(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {{\n",
+        name
+    ));
+    src.push_str("    use crate::intcode::aot::Vm;\n\n");
+
+    src.push_str("    const CODE: &[crate::intcode::Word] = &[");
+    for (i, word) in code.iter().enumerate() {
+        if i % 16 == 0 {
+            src.push_str("\n       ");
+        }
+        src.push_str(&format!(" {},", Value::Const(*word).text()));
+    }
+    src.push_str("\n    ];\n");
+
+    let ranges: Vec<_> = covered.iter().map(|range| format!("{}..{}", range.start, range.end)).collect();
+    src.push_str(&format!("    const COVERED: &[std::ops::Range<usize>] = &[{}];\n\n", ranges.join(", ")));
+
+    src.push_str("    if !Vm::can_run(computer, CODE, COVERED) {\n");
+    src.push_str("        return computer.run();\n");
+    src.push_str("    }\n\n");
+    src.push_str("    let mut vm = Vm::new(computer, COVERED);\n");
+    src.push_str("    loop {\n");
+    src.push_str("        match vm.pc() {\n");
+
+    for (pc, instruction) in &instructions {
+        let mut arm = Arm {
+            pc: *pc,
+            instruction,
+            covered: &covered,
+            lines: Vec::new(),
+        };
+
+        if arm.generate().is_none() {
+            arm.lines = vec!["return vm.interpret();".to_string()];
+        }
+
+        src.push_str(&format!("            // {}\n", instruction));
+        src.push_str(&format!("            {} => {{\n", pc));
+        for line in arm.lines {
+            src.push_str(&format!("                {}\n", line));
+        }
+        src.push_str("            }\n");
+    }
+
+    src.push_str("            _ => return vm.interpret(),\n");
+    src.push_str("        }\n");
+    src.push_str("    }\n");
+    src.push_str("}\n");
+    src
+}
+
+#[cfg(test)]
+mod samples;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::from_str;
+    use std::env;
+    use std::fs;
+
+    /// the example programs from days 5 and 9
+    const SAMPLES: &[(&str, &str)] = &[
+        ("day5_echo", "3,0,4,0,99"),
+        ("day5_mul", "1002,4,3,4,33"),
+        ("day5_eq_pointer", "3,9,8,9,10,9,4,9,99,-1,8"),
+        ("day5_lt_pointer", "3,9,7,9,10,9,4,9,99,-1,8"),
+        ("day5_eq_immediate", "3,3,1108,-1,8,3,4,3,99"),
+        ("day5_lt_immediate", "3,3,1107,-1,8,3,4,3,99"),
+        ("day5_jump_pointer", "3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9"),
+        ("day5_jump_immediate", "3,3,1105,-1,9,1101,0,0,12,4,12,99,1"),
+        ("day5_compare_8", "3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,\
+            1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,999,1105,1,46,1101,1000,1,20,4,20,\
+            1105,1,46,98,99"),
+        ("day9_quine", "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99"),
+        ("day9_big_mul", "1102,34915192,34915192,7,4,7,99,0"),
+        ("day9_big_out", "104,1125899906842624,99"),
+        ("overflow_const", "1102,9223372036854775807,2,7,4,7,99,0"),
+        ("overflow_add", "3,13,1001,13,9223372036854775807,13,1002,13,3,13,4,13,99,0"),
+        ("overflow_rel_base", "109,9223372036854775807,109,9223372036854775807,204,0,99"),
+        ("overflow_rel_addr", "109,1,204,9223372036854775807,99"),
+    ];
+
+    fn generate_samples() -> String {
+        let mut src = String::from("// generated by `intcode::aot::test::generate_samples`, don't edit\n\n");
+        for (name, code) in SAMPLES {
+            src.push_str(&transpile(&from_str(code), name));
+            src.push('\n');
+        }
+
+        src.push_str("pub type Transpiled = fn(&mut crate::intcode::Computer) -> crate::intcode::ExecResult<()>;\n\n");
+        src.push_str("pub const SAMPLES: &[(&str, Transpiled)] = &[\n");
+        for (name, _) in SAMPLES {
+            src.push_str(&format!("    (\"{}\", {}),\n", name, name));
+        }
+        src.push_str("];\n");
+        src
+    }
+
+    #[test]
+    fn generated_samples_are_up_to_date() {
+        let src = generate_samples();
+        if env::var_os("INTCODE_AOT_BLESS").is_some() {
+            fs::write(concat!(env!("CARGO_MANIFEST_DIR"), "/src/intcode/aot/samples.rs"), &src).unwrap();
+            return;
+        }
+
+        assert!(
+            src == include_str!("aot/samples.rs"),
+            "transpiled samples are out of date, regenerate them with INTCODE_AOT_BLESS=1 cargo test"
+        );
+    }
+
+    #[test]
+    fn matches_interpreter_on_samples() {
+        for ((name, code), (_, transpiled)) in SAMPLES.iter().zip(samples::SAMPLES) {
+            for input in -1..=10 {
+                let mut expected = Computer::new(from_str(code));
+                let mut actual = Computer::new(from_str(code));
+
+                // run without input first, so programs that read input have to suspend and resume
+                let blocked = expected.run();
+                assert_eq!(transpiled(&mut actual), blocked, "{}", name);
+
+                expected.in_buf.push_back(input);
+                actual.in_buf.push_back(input);
+                if blocked.is_err() {
+                    assert_eq!(transpiled(&mut actual), expected.run(), "{} with input {}", name, input);
+                }
+
+                assert_eq!(actual.out_buf, expected.out_buf, "{} with input {}", name, input);
+                assert_eq!(actual.pc(), expected.pc(), "{} with input {}", name, input);
+                assert_eq!(actual.snapshot().to_text(), expected.snapshot().to_text(), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn wraps_like_interpreter() {
+        let sample = |name| samples::SAMPLES.iter().find(|(sample, _)| *sample == name).unwrap().1;
+
+        let mut computer = Computer::new(from_str("1102,9223372036854775807,2,7,4,7,99,0"));
+        assert_eq!(sample("overflow_const")(&mut computer), Ok(()));
+        assert_eq!(computer.out_buf, [-2]);
+
+        let mut computer = Computer::new(from_str("3,13,1001,13,9223372036854775807,13,1002,13,3,13,4,13,99,0"));
+        computer.in_buf.push_back(2);
+        assert_eq!(sample("overflow_add")(&mut computer), Ok(()));
+        assert_eq!(computer.out_buf, [Word::MIN.wrapping_add(1).wrapping_mul(3)]);
+
+        let mut computer = Computer::new(from_str("109,9223372036854775807,109,9223372036854775807,204,0,99"));
+        let result = sample("overflow_rel_base")(&mut computer);
+        assert_eq!(result, Err(ExecError::NegativeAddress { pc: 4, word: 204, addr: -2 }));
+    }
+}
@@ -0,0 +1,751 @@
+// generated by `intcode::aot::test::generate_samples`, don't edit
+
+/// transpiled from an intcode program of 5 words
+#[allow(unreachable_code, clippy::all)]
+pub fn day5_echo(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        3, 0, 4, 0, 99,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..5];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // IN [0]
+            0 => {
+                let input = vm.input()?;
+                let modified = vm.store(0, input);
+                vm.goto(2);
+                if modified { return vm.interpret(); }
+            }
+            // OUT [0]
+            2 => {
+                let a = vm.load(0);
+                vm.output(a);
+                vm.goto(4);
+            }
+            // HALT
+            4 => {
+                return Ok(());
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+/// transpiled from an intcode program of 5 words
+#[allow(unreachable_code, clippy::all)]
+pub fn day5_mul(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        1002, 4, 3, 4, 33,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..4];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // MUL [4], #3, [4]
+            0 => {
+                let a = vm.load(4);
+                vm.store(4, crate::intcode::Word::wrapping_mul(a, 3));
+                vm.goto(4);
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+/// transpiled from an intcode program of 11 words
+#[allow(unreachable_code, clippy::all)]
+pub fn day5_eq_pointer(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..9];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // IN [9]
+            0 => {
+                let input = vm.input()?;
+                vm.store(9, input);
+                vm.goto(2);
+            }
+            // EQ [9], [10], [9]
+            2 => {
+                let a = vm.load(9);
+                let b = vm.load(10);
+                vm.store(9, if a == b { 1 } else { 0 });
+                vm.goto(6);
+            }
+            // OUT [9]
+            6 => {
+                let a = vm.load(9);
+                vm.output(a);
+                vm.goto(8);
+            }
+            // HALT
+            8 => {
+                return Ok(());
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+/// transpiled from an intcode program of 11 words
+#[allow(unreachable_code, clippy::all)]
+pub fn day5_lt_pointer(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..9];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // IN [9]
+            0 => {
+                let input = vm.input()?;
+                vm.store(9, input);
+                vm.goto(2);
+            }
+            // LT [9], [10], [9]
+            2 => {
+                let a = vm.load(9);
+                let b = vm.load(10);
+                vm.store(9, if a < b { 1 } else { 0 });
+                vm.goto(6);
+            }
+            // OUT [9]
+            6 => {
+                let a = vm.load(9);
+                vm.output(a);
+                vm.goto(8);
+            }
+            // HALT
+            8 => {
+                return Ok(());
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+/// transpiled from an intcode program of 9 words
+#[allow(unreachable_code, clippy::all)]
+pub fn day5_eq_immediate(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        3, 3, 1108, -1, 8, 3, 4, 3, 99,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..9];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // IN [3]
+            0 => {
+                let input = vm.input()?;
+                let modified = vm.store(3, input);
+                vm.goto(2);
+                if modified { return vm.interpret(); }
+            }
+            // EQ #-1, #8, [3]
+            2 => {
+                let modified = vm.store(3, 0);
+                vm.goto(6);
+                if modified { return vm.interpret(); }
+            }
+            // OUT [3]
+            6 => {
+                let a = vm.load(3);
+                vm.output(a);
+                vm.goto(8);
+            }
+            // HALT
+            8 => {
+                return Ok(());
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+/// transpiled from an intcode program of 9 words
+#[allow(unreachable_code, clippy::all)]
+pub fn day5_lt_immediate(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        3, 3, 1107, -1, 8, 3, 4, 3, 99,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..9];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // IN [3]
+            0 => {
+                let input = vm.input()?;
+                let modified = vm.store(3, input);
+                vm.goto(2);
+                if modified { return vm.interpret(); }
+            }
+            // LT #-1, #8, [3]
+            2 => {
+                let modified = vm.store(3, 1);
+                vm.goto(6);
+                if modified { return vm.interpret(); }
+            }
+            // OUT [3]
+            6 => {
+                let a = vm.load(3);
+                vm.output(a);
+                vm.goto(8);
+            }
+            // HALT
+            8 => {
+                return Ok(());
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+/// transpiled from an intcode program of 16 words
+#[allow(unreachable_code, clippy::all)]
+pub fn day5_jump_pointer(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..12];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // IN [12]
+            0 => {
+                let input = vm.input()?;
+                vm.store(12, input);
+                vm.goto(2);
+            }
+            // JZ [12], [15]
+            2 => {
+                let a = vm.load(12);
+                let b = vm.load(15);
+                if a == 0 { if !vm.jump(b) { return vm.interpret(); } } else { vm.goto(5); }
+            }
+            // ADD [13], [14], [13]
+            5 => {
+                let a = vm.load(13);
+                let b = vm.load(14);
+                vm.store(13, crate::intcode::Word::wrapping_add(a, b));
+                vm.goto(9);
+            }
+            // OUT [13]
+            9 => {
+                let a = vm.load(13);
+                vm.output(a);
+                vm.goto(11);
+            }
+            // HALT
+            11 => {
+                return Ok(());
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+/// transpiled from an intcode program of 13 words
+#[allow(unreachable_code, clippy::all)]
+pub fn day5_jump_immediate(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..5, 9..12];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // IN [3]
+            0 => {
+                let input = vm.input()?;
+                let modified = vm.store(3, input);
+                vm.goto(2);
+                if modified { return vm.interpret(); }
+            }
+            // JNZ #-1, #9
+            2 => {
+                vm.goto(9);
+            }
+            // OUT [12]
+            9 => {
+                let a = vm.load(12);
+                vm.output(a);
+                vm.goto(11);
+            }
+            // HALT
+            11 => {
+                return Ok(());
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+/// transpiled from an intcode program of 47 words
+#[allow(unreachable_code, clippy::all)]
+pub fn day5_compare_8(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31,
+        1106, 0, 36, 98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104,
+        999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105, 1, 46, 98, 99,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..19, 22..45, 46..47];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // IN [21]
+            0 => {
+                let input = vm.input()?;
+                vm.store(21, input);
+                vm.goto(2);
+            }
+            // EQ [21], #8, [20]
+            2 => {
+                let a = vm.load(21);
+                vm.store(20, if a == 8 { 1 } else { 0 });
+                vm.goto(6);
+            }
+            // JNZ [20], #22
+            6 => {
+                let a = vm.load(20);
+                if a != 0 { vm.goto(22); } else { vm.goto(9); }
+            }
+            // LT #8, [21], [20]
+            9 => {
+                let b = vm.load(21);
+                vm.store(20, if 8 < b { 1 } else { 0 });
+                vm.goto(13);
+            }
+            // JZ [20], #31
+            13 => {
+                let a = vm.load(20);
+                if a == 0 { vm.goto(31); } else { vm.goto(16); }
+            }
+            // JZ #0, #36
+            16 => {
+                vm.goto(36);
+            }
+            // MUL [21], #125, [20]
+            22 => {
+                let a = vm.load(21);
+                vm.store(20, crate::intcode::Word::wrapping_mul(a, 125));
+                vm.goto(26);
+            }
+            // OUT [20]
+            26 => {
+                let a = vm.load(20);
+                vm.output(a);
+                vm.goto(28);
+            }
+            // JNZ #1, #46
+            28 => {
+                vm.goto(46);
+            }
+            // OUT #999
+            31 => {
+                vm.output(999);
+                vm.goto(33);
+            }
+            // JNZ #1, #46
+            33 => {
+                vm.goto(46);
+            }
+            // ADD #1000, #1, [20]
+            36 => {
+                vm.store(20, 1001);
+                vm.goto(40);
+            }
+            // OUT [20]
+            40 => {
+                let a = vm.load(20);
+                vm.output(a);
+                vm.goto(42);
+            }
+            // JNZ #1, #46
+            42 => {
+                vm.goto(46);
+            }
+            // HALT
+            46 => {
+                return Ok(());
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+/// transpiled from an intcode program of 16 words
+#[allow(unreachable_code, clippy::all)]
+pub fn day9_quine(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..16];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // ARB #1
+            0 => {
+                if !vm.offset(1) { return vm.interpret(); }
+                vm.goto(2);
+            }
+            // OUT rb-1
+            2 => {
+                let a = match vm.rel_addr(-1) { Some(addr) => vm.load(addr), None => return vm.interpret() };
+                vm.output(a);
+                vm.goto(4);
+            }
+            // ADD [100], #1, [100]
+            4 => {
+                let a = vm.load(100);
+                vm.store(100, crate::intcode::Word::wrapping_add(a, 1));
+                vm.goto(8);
+            }
+            // EQ [100], #16, [101]
+            8 => {
+                let a = vm.load(100);
+                vm.store(101, if a == 16 { 1 } else { 0 });
+                vm.goto(12);
+            }
+            // JZ [101], #0
+            12 => {
+                let a = vm.load(101);
+                if a == 0 { vm.goto(0); } else { vm.goto(15); }
+            }
+            // HALT
+            15 => {
+                return Ok(());
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+/// transpiled from an intcode program of 8 words
+#[allow(unreachable_code, clippy::all)]
+pub fn day9_big_mul(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        1102, 34915192, 34915192, 7, 4, 7, 99, 0,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..7];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // MUL #34915192, #34915192, [7]
+            0 => {
+                vm.store(7, 1219070632396864);
+                vm.goto(4);
+            }
+            // OUT [7]
+            4 => {
+                let a = vm.load(7);
+                vm.output(a);
+                vm.goto(6);
+            }
+            // HALT
+            6 => {
+                return Ok(());
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+/// transpiled from an intcode program of 3 words
+#[allow(unreachable_code, clippy::all)]
+pub fn day9_big_out(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        104, 1125899906842624, 99,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..3];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // OUT #1125899906842624
+            0 => {
+                vm.output(1125899906842624);
+                vm.goto(2);
+            }
+            // HALT
+            2 => {
+                return Ok(());
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+/// transpiled from an intcode program of 8 words
+#[allow(unreachable_code, clippy::all)]
+pub fn overflow_const(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        1102, 9223372036854775807, 2, 7, 4, 7, 99, 0,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..7];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // MUL #9223372036854775807, #2, [7]
+            0 => {
+                vm.store(7, -2);
+                vm.goto(4);
+            }
+            // OUT [7]
+            4 => {
+                let a = vm.load(7);
+                vm.output(a);
+                vm.goto(6);
+            }
+            // HALT
+            6 => {
+                return Ok(());
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+/// transpiled from an intcode program of 14 words
+#[allow(unreachable_code, clippy::all)]
+pub fn overflow_add(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        3, 13, 1001, 13, 9223372036854775807, 13, 1002, 13, 3, 13, 4, 13, 99, 0,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..13];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // IN [13]
+            0 => {
+                let input = vm.input()?;
+                vm.store(13, input);
+                vm.goto(2);
+            }
+            // ADD [13], #9223372036854775807, [13]
+            2 => {
+                let a = vm.load(13);
+                vm.store(13, crate::intcode::Word::wrapping_add(a, 9223372036854775807));
+                vm.goto(6);
+            }
+            // MUL [13], #3, [13]
+            6 => {
+                let a = vm.load(13);
+                vm.store(13, crate::intcode::Word::wrapping_mul(a, 3));
+                vm.goto(10);
+            }
+            // OUT [13]
+            10 => {
+                let a = vm.load(13);
+                vm.output(a);
+                vm.goto(12);
+            }
+            // HALT
+            12 => {
+                return Ok(());
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+/// transpiled from an intcode program of 7 words
+#[allow(unreachable_code, clippy::all)]
+pub fn overflow_rel_base(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        109, 9223372036854775807, 109, 9223372036854775807, 204, 0, 99,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..7];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // ARB #9223372036854775807
+            0 => {
+                if !vm.offset(9223372036854775807) { return vm.interpret(); }
+                vm.goto(2);
+            }
+            // ARB #9223372036854775807
+            2 => {
+                if !vm.offset(9223372036854775807) { return vm.interpret(); }
+                vm.goto(4);
+            }
+            // OUT rb+0
+            4 => {
+                let a = match vm.rel_addr(0) { Some(addr) => vm.load(addr), None => return vm.interpret() };
+                vm.output(a);
+                vm.goto(6);
+            }
+            // HALT
+            6 => {
+                return Ok(());
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+/// transpiled from an intcode program of 5 words
+#[allow(unreachable_code, clippy::all)]
+pub fn overflow_rel_addr(computer: &mut crate::intcode::Computer) -> crate::intcode::ExecResult<()> {
+    use crate::intcode::aot::Vm;
+
+    const CODE: &[crate::intcode::Word] = &[
+        109, 1, 204, 9223372036854775807, 99,
+    ];
+    const COVERED: &[std::ops::Range<usize>] = &[0..5];
+
+    if !Vm::can_run(computer, CODE, COVERED) {
+        return computer.run();
+    }
+
+    let mut vm = Vm::new(computer, COVERED);
+    loop {
+        match vm.pc() {
+            // ARB #1
+            0 => {
+                if !vm.offset(1) { return vm.interpret(); }
+                vm.goto(2);
+            }
+            // OUT rb+9223372036854775807
+            2 => {
+                let a = match vm.rel_addr(9223372036854775807) { Some(addr) => vm.load(addr), None => return vm.interpret() };
+                vm.output(a);
+                vm.goto(4);
+            }
+            // HALT
+            4 => {
+                return Ok(());
+            }
+            _ => return vm.interpret(),
+        }
+    }
+}
+
+pub type Transpiled = fn(&mut crate::intcode::Computer) -> crate::intcode::ExecResult<()>;
+
+pub const SAMPLES: &[(&str, Transpiled)] = &[
+    ("day5_echo", day5_echo),
+    ("day5_mul", day5_mul),
+    ("day5_eq_pointer", day5_eq_pointer),
+    ("day5_lt_pointer", day5_lt_pointer),
+    ("day5_eq_immediate", day5_eq_immediate),
+    ("day5_lt_immediate", day5_lt_immediate),
+    ("day5_jump_pointer", day5_jump_pointer),
+    ("day5_jump_immediate", day5_jump_immediate),
+    ("day5_compare_8", day5_compare_8),
+    ("day9_quine", day9_quine),
+    ("day9_big_mul", day9_big_mul),
+    ("day9_big_out", day9_big_out),
+    ("overflow_const", overflow_const),
+    ("overflow_add", overflow_add),
+    ("overflow_rel_base", overflow_rel_base),
+    ("overflow_rel_addr", overflow_rel_addr),
+];
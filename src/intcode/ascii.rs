@@ -0,0 +1,82 @@
+//! helpers for programs which read and write ASCII text, one character per word
+
+use super::{Word, Computer};
+use super::word::IntcodeWord;
+use std::mem;
+
+/// values in this range are characters, anything else is a number like a puzzle answer
+const ASCII: std::ops::Range<i64> = 0..128;
+
+/// output from a program, split into its text and the values which weren't ASCII characters
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct AsciiOutput<W = Word> {
+    pub text: String,
+    pub values: Vec<W>,
+}
+
+impl<W: IntcodeWord> AsciiOutput<W> {
+    pub fn from_words(words: impl IntoIterator<Item=W>) -> Self {
+        let mut output = AsciiOutput {
+            text: String::new(),
+            values: Vec::new(),
+        };
+
+        for word in words {
+            match word.to_i64() {
+                Some(c) if ASCII.contains(&c) => output.text.push(c as u8 as char),
+                _ => output.values.push(word),
+            }
+        }
+
+        output
+    }
+}
+
+impl<W: IntcodeWord> Computer<W> {
+    /// add each character of `line` to the input buffer, followed by a newline. characters
+    /// outside ASCII are added as their unicode code point
+    pub fn push_line(&mut self, line: &str) {
+        let chars = line.chars().chain(Some('\n'));
+        self.in_buf.extend(chars.map(|c| W::from_i64(c as i64)));
+    }
+
+    /// take everything in the output buffer
+    pub fn take_ascii_output(&mut self) -> AsciiOutput<W> {
+        AsciiOutput::from_words(mem::take(&mut self.out_buf))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{ExecError, asm::assemble};
+
+    #[test]
+    fn separates_text_from_values() {
+        // echoes each line, followed by 1000 plus its length
+        let code = assemble("
+            loop:   IN [c]
+                    OUT [c]
+                    EQ [c], #10, [done]
+                    JNZ [done], eol
+                    ADD [n], #1, [n]
+                    JZ #0, loop
+            eol:    ADD [n], #1000, [n]
+                    OUT [n]
+                    HALT
+            c:      db 0
+            n:      db 0
+            done:   db 0
+        ").unwrap();
+
+        let mut computer = Computer::new(code);
+        assert_eq!(computer.run(), Err(ExecError::InputBlocked));
+        assert_eq!(computer.take_ascii_output().text, "");
+
+        computer.push_line("hi!");
+        assert_eq!(computer.in_buf, [104, 105, 33, 10]);
+        assert_eq!(computer.run(), Ok(()));
+        assert_eq!(computer.take_ascii_output(), AsciiOutput { text: "hi!\n".to_string(), values: vec![1003] });
+        assert!(computer.out_buf.is_empty());
+    }
+}
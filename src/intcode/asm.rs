@@ -0,0 +1,616 @@
+//! assembler for intcode mnemonic source, in the same syntax the disassembler produces:
+//!
+//! ```text
+//! ; comments run to the end of the line
+//! start:  IN [value]          ; [addr] reads from address, #val is immediate,
+//!         OUT rb-1            ; rb+n is relative to the relative base
+//!         JNZ [value], start  ; a bare label or number is an immediate value
+//!         HALT
+//! value:  db 0, 1, start+2    ; data words
+//!         ds 4                ; four zero words
+//! ```
+
+use super::{Word, Op, Mode, OpCode};
+use super::symbols::SymbolTable;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum AsmErrorKind {
+    UnexpectedChar(char),
+    UnexpectedEnd,
+    BadNumber(String),
+    UnknownMnemonic(String),
+    WrongOperandCount { expected: usize, found: usize },
+    ImmediateWrite,
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    Overflow,
+    TooLarge,
+}
+
+/// an error in assembler source, at a 1-based line and column
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct AsmError {
+    pub line: usize,
+    pub col: usize,
+    pub kind: AsmErrorKind,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.col)?;
+
+        match &self.kind {
+            AsmErrorKind::UnexpectedChar(c) => write!(f, "unexpected character '{}'", c),
+            AsmErrorKind::UnexpectedEnd => write!(f, "unexpected end of line"),
+            AsmErrorKind::BadNumber(num) => write!(f, "bad number: {}", num),
+            AsmErrorKind::UnknownMnemonic(name) => write!(f, "unknown mnemonic: {}", name),
+            AsmErrorKind::WrongOperandCount { expected, found } => {
+                write!(f, "expected {} operands, found {}", expected, found)
+            }
+            AsmErrorKind::ImmediateWrite => write!(f, "output operand can't be immediate"),
+            AsmErrorKind::UndefinedLabel(name) => write!(f, "undefined label: {}", name),
+            AsmErrorKind::DuplicateLabel(name) => write!(f, "label already defined: {}", name),
+            AsmErrorKind::Overflow => write!(f, "value doesn't fit in a word"),
+            AsmErrorKind::TooLarge => write!(f, "program is longer than {} words", MAX_LEN),
+        }
+    }
+}
+
+impl Error for AsmError {
+}
+
+pub type AsmResult<T> = Result<T, AsmError>;
+
+/// the most words a program can assemble to, so that a typo in a `ds` can't use up all the memory
+pub const MAX_LEN: usize = 1 << 24;
+
+#[derive(Clone, Debug)]
+struct LabelRef {
+    name: String,
+    line: usize,
+    col: usize,
+}
+
+/// a sum of a constant and any number of label addresses, each added or subtracted
+#[derive(Clone, Debug)]
+struct Expr {
+    constant: Word,
+    labels: Vec<(Word, LabelRef)>,
+}
+
+impl Expr {
+    /// the negated expression, or `None` if the constant can't be negated
+    fn negate(mut self) -> Option<Self> {
+        self.constant = self.constant.checked_neg()?;
+        for (sign, _) in &mut self.labels {
+            *sign = -*sign;
+        }
+        Some(self)
+    }
+
+    fn eval(&self, labels: &HashMap<String, usize>) -> AsmResult<Word> {
+        self.labels.iter().try_fold(self.constant, |val, (sign, label)| {
+            let err = |kind| AsmError { line: label.line, col: label.col, kind };
+            let addr = labels.get(&label.name)
+                .ok_or_else(|| err(AsmErrorKind::UndefinedLabel(label.name.clone())))?;
+
+            (*addr as Word).checked_mul(*sign)
+                .and_then(|addr| val.checked_add(addr))
+                .ok_or_else(|| err(AsmErrorKind::Overflow))
+        })
+    }
+}
+
+enum Statement {
+    Instruction { op: Op, operands: Vec<(Mode, Expr)> },
+    Data(Vec<Expr>),
+}
+
+impl Statement {
+    fn size(&self) -> usize {
+        match self {
+            Statement::Instruction { operands, .. } => 1 + operands.len(),
+            Statement::Data(words) => words.len(),
+        }
+    }
+}
+
+struct LineParser<'a> {
+    line: usize,
+    chars: Vec<(usize, char)>,
+    pos: usize,
+    src: &'a str,
+}
+
+impl<'a> LineParser<'a> {
+    fn new(line: usize, src: &'a str) -> Self {
+        // everything after a ; is a comment
+        let src = match src.find(';') {
+            Some(comment) => &src[..comment],
+            None => src,
+        };
+
+        Self {
+            line,
+            chars: src.char_indices().collect(),
+            pos: 0,
+            src,
+        }
+    }
+
+    fn col(&self) -> usize {
+        let offset = self.chars.get(self.pos).map(|(offset, _)| *offset).unwrap_or(self.src.len());
+        self.src[..offset].chars().count() + 1
+    }
+
+    fn error(&self, kind: AsmErrorKind) -> AsmError {
+        AsmError {
+            line: self.line,
+            col: self.col(),
+            kind,
+        }
+    }
+
+    fn unexpected(&self) -> AsmError {
+        match self.peek() {
+            Some(c) => self.error(AsmErrorKind::UnexpectedChar(c)),
+            None => self.error(AsmErrorKind::UnexpectedEnd),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map(char::is_whitespace).unwrap_or(false) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).map(|(_, c)| *c)
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.peek().is_none()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> AsmResult<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> String {
+        let mut taken = String::new();
+        while let Some(c) = self.peek().filter(|c| pred(*c)) {
+            taken.push(c);
+            self.pos += 1;
+        }
+        taken
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.' => {
+                Some(self.take_while(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            }
+            _ => None,
+        }
+    }
+
+    /// parse a number, which can be any type that holds the digits
+    fn number<T: std::str::FromStr>(&mut self) -> AsmResult<T> {
+        self.skip_whitespace();
+        let col = self.col();
+        let digits = self.take_while(|c| c.is_ascii_alphanumeric());
+        if digits.is_empty() {
+            return Err(self.unexpected());
+        }
+
+        digits.parse().map_err(|_| AsmError {
+            line: self.line,
+            col,
+            kind: AsmErrorKind::BadNumber(digits),
+        })
+    }
+
+    fn term(&mut self, sign: Word, expr: &mut Expr) -> AsmResult<()> {
+        self.skip_whitespace();
+        let col = self.col();
+
+        match self.ident() {
+            Some(name) => {
+                expr.labels.push((sign, LabelRef { name, line: self.line, col }));
+            }
+
+            None => {
+                // adding the magnitude with a wider type lets the constant reach the lowest word
+                let num: u64 = self.number()?;
+                let sum = i128::from(expr.constant) + i128::from(sign) * i128::from(num);
+                expr.constant = Word::try_from(sum).map_err(|_| AsmError {
+                    line: self.line,
+                    col,
+                    kind: AsmErrorKind::Overflow,
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn expr(&mut self) -> AsmResult<Expr> {
+        let mut expr = Expr { constant: 0, labels: Vec::new() };
+
+        let sign = if self.eat('-') { -1 } else { 1 };
+        self.term(sign, &mut expr)?;
+
+        loop {
+            if self.eat('+') {
+                self.term(1, &mut expr)?;
+            } else if self.eat('-') {
+                self.term(-1, &mut expr)?;
+            } else {
+                break Ok(expr);
+            }
+        }
+    }
+
+    fn operand(&mut self) -> AsmResult<(Mode, Expr)> {
+        if self.eat('[') {
+            let expr = self.expr()?;
+            self.expect(']')?;
+            return Ok((Mode::Pointer, expr));
+        }
+
+        if self.eat('#') {
+            return Ok((Mode::Immediate, self.expr()?));
+        }
+
+        // rb, rb+n or rb-n
+        let start = self.pos;
+        if self.ident().as_deref() == Some("rb") {
+            let offset = if self.eat('+') {
+                self.expr()?
+            } else if self.eat('-') {
+                self.skip_whitespace();
+                let col = self.col();
+                self.expr()?.negate().ok_or(AsmError {
+                    line: self.line,
+                    col,
+                    kind: AsmErrorKind::Overflow,
+                })?
+            } else {
+                Expr { constant: 0, labels: Vec::new() }
+            };
+
+            return Ok((Mode::Relative, offset));
+        }
+        self.pos = start;
+
+        Ok((Mode::Immediate, self.expr()?))
+    }
+
+    fn list<T>(&mut self, mut item: impl FnMut(&mut Self) -> AsmResult<T>) -> AsmResult<Vec<T>> {
+        let mut items = Vec::new();
+        if self.at_end() {
+            return Ok(items);
+        }
+
+        loop {
+            items.push(item(self)?);
+            if !self.eat(',') {
+                break;
+            }
+        }
+
+        if self.at_end() {
+            Ok(items)
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// parse a line into its label, if it has one, and its statement, if it has one
+    fn parse(&mut self) -> AsmResult<(Option<LabelRef>, Option<Statement>)> {
+        self.skip_whitespace();
+        let col = self.col();
+
+        let mut name = match self.ident() {
+            Some(name) => name,
+            None if self.at_end() => return Ok((None, None)),
+            None => return Err(self.unexpected()),
+        };
+
+        let mut label = None;
+        let mut name_col = col;
+        if self.eat(':') {
+            label = Some(LabelRef { name, line: self.line, col });
+
+            self.skip_whitespace();
+            name_col = self.col();
+            name = match self.ident() {
+                Some(name) => name,
+                None if self.at_end() => return Ok((label, None)),
+                None => return Err(self.unexpected()),
+            };
+        }
+
+        let statement = match name.to_ascii_lowercase().as_str() {
+            "db" => Statement::Data(self.list(Self::expr)?),
+
+            "ds" => {
+                self.skip_whitespace();
+                let col = self.col();
+                let count: Word = self.number()?;
+                if !self.at_end() {
+                    return Err(self.unexpected());
+                }
+                if count > MAX_LEN as Word {
+                    return Err(AsmError { line: self.line, col, kind: AsmErrorKind::TooLarge });
+                }
+
+                let zero = Expr { constant: 0, labels: Vec::new() };
+                Statement::Data(vec![zero; count.max(0) as usize])
+            }
+
+            _ => {
+                let op = Op::from_mnemonic(&name).ok_or_else(|| AsmError {
+                    line: self.line,
+                    col: name_col,
+                    kind: AsmErrorKind::UnknownMnemonic(name.clone()),
+                })?;
+
+                let operands = self.list(Self::operand)?;
+                if operands.len() != op.param_count() {
+                    return Err(AsmError {
+                        line: self.line,
+                        col: name_col,
+                        kind: AsmErrorKind::WrongOperandCount {
+                            expected: op.param_count(),
+                            found: operands.len(),
+                        },
+                    });
+                }
+
+                let writes_immediate = op.out_param()
+                    .map(|param| operands[param].0 == Mode::Immediate)
+                    .unwrap_or(false);
+                if writes_immediate {
+                    return Err(AsmError {
+                        line: self.line,
+                        col: name_col,
+                        kind: AsmErrorKind::ImmediateWrite,
+                    });
+                }
+
+                Statement::Instruction { op, operands }
+            }
+        };
+
+        Ok((label, Some(statement)))
+    }
+}
+
+/// assemble mnemonic source into a program that can be passed to `Computer::new`
+pub fn assemble(source: &str) -> AsmResult<Vec<Word>> {
+    assemble_with_symbols(source).map(|(code, _)| code)
+}
+
+/// assemble a program, along with a symbol table of its labels and the addresses each source
+/// line assembled to. labels followed by data are variables covering all the data up to the next
+/// label or instruction
+pub fn assemble_with_symbols(source: &str) -> AsmResult<(Vec<Word>, SymbolTable)> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut symbols = SymbolTable::new();
+    let mut addr = 0;
+
+    // the label being defined, with the address and length of the data following it
+    let mut pending: Option<(String, usize)> = None;
+    let mut data_len = 0;
+
+    for (line_index, line) in source.lines().enumerate() {
+        let (label, statement) = LineParser::new(line_index + 1, line).parse()?;
+
+        if let Some(label) = label {
+            if labels.insert(label.name.clone(), addr).is_some() {
+                return Err(AsmError {
+                    line: label.line,
+                    col: label.col,
+                    kind: AsmErrorKind::DuplicateLabel(label.name),
+                });
+            }
+
+            add_symbol(&mut symbols, pending.take(), data_len);
+            pending = Some((label.name, addr));
+            data_len = 0;
+        }
+
+        if let Some(statement) = statement {
+            match &statement {
+                Statement::Data(_) if pending.is_some() => data_len += statement.size(),
+                _ => {
+                    add_symbol(&mut symbols, pending.take(), data_len);
+                    data_len = 0;
+                }
+            }
+
+            if addr + statement.size() > MAX_LEN {
+                return Err(AsmError { line: line_index + 1, col: 1, kind: AsmErrorKind::TooLarge });
+            }
+            if statement.size() > 0 {
+                symbols.add_line(line_index + 1, addr..addr + statement.size());
+            }
+            addr += statement.size();
+            statements.push(statement);
+        }
+    }
+    add_symbol(&mut symbols, pending, data_len);
+
+    let mut code = Vec::with_capacity(addr);
+    for statement in statements {
+        match statement {
+            Statement::Instruction { op, operands } => {
+                let modes: Vec<_> = operands.iter().map(|(mode, _)| *mode).collect();
+                code.push(OpCode::encode(op, &modes));
+
+                for (_, expr) in operands {
+                    code.push(expr.eval(&labels)?);
+                }
+            }
+
+            Statement::Data(words) => {
+                for expr in words {
+                    code.push(expr.eval(&labels)?);
+                }
+            }
+        }
+    }
+
+    Ok((code, symbols))
+}
+
+/// add a label, which is a variable if `data_len` words of data followed it
+fn add_symbol(symbols: &mut SymbolTable, label: Option<(String, usize)>, data_len: usize) {
+    match label {
+        Some((name, addr)) if data_len > 0 => symbols.add_variable(&name, addr, data_len),
+        Some((name, addr)) => symbols.add_label(&name, addr),
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{from_str, disasm, Computer};
+    use crate::intcode::symbols::SymbolKind;
+
+    #[test]
+    fn assembles_modes_and_labels() {
+        let code = assemble("
+            ; multiplying the value by 3 turns it into a halt instruction
+            mul [value], #3, [value]
+        value:
+            db 33
+        ").unwrap();
+
+        assert_eq!(code, from_str("1002,4,3,4,33"));
+    }
+
+    #[test]
+    fn assembles_quine() {
+        let code = assemble("
+            loop:   ARB #1
+                    OUT rb-1
+                    ADD [counter], #1, [counter]
+                    EQ [counter], #16, [done]
+                    JZ [done], loop
+                    HALT
+                    ds 84
+            counter: db 0
+            done:   db 0
+        ").unwrap();
+
+        let quine = from_str("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99");
+        assert_eq!(code[..16], quine[..]);
+
+        let mut computer = Computer::new(code);
+        computer.run().unwrap();
+        assert_eq!(computer.out_buf, quine);
+    }
+
+    #[test]
+    fn round_trips_through_disassembler() {
+        let code = from_str("3,9,1005,9,7,99,42,204,-1,0,109,19,21101,1,2,3,1106,0,0");
+        let listing = disasm::disassemble(&code).to_string();
+
+        assert_eq!(assemble(&listing), Ok(code));
+    }
+
+    #[test]
+    fn round_trips_unused_mode_digits() {
+        let code = from_str("20099,1104,5,104,5,99");
+        let listing = disasm::disassemble(&code).to_string();
+
+        assert!(listing.contains("db 1104"));
+        assert!(listing.contains("db 20099"));
+        assert_eq!(assemble(&listing), Ok(code));
+    }
+
+    #[test]
+    fn records_symbols() {
+        let (code, symbols) = assemble_with_symbols("
+            start:  IN [buf+1]
+            loop:   OUT [buf+1]
+                    JNZ [buf+1], loop
+                    HALT
+            buf:    db 1
+                    db 2, 3
+        ").unwrap();
+
+        let kinds: Vec<_> = symbols.symbols().iter()
+            .map(|symbol| (&symbol.name[..], symbol.kind, symbol.addr, symbol.len))
+            .collect();
+        assert_eq!(kinds, [
+            ("start", SymbolKind::Label, 0, 0),
+            ("loop", SymbolKind::Label, 2, 0),
+            ("buf", SymbolKind::Variable, 8, 3),
+        ]);
+        assert_eq!(symbols.source_line(5), Some(4));
+        assert_eq!(symbols.describe(3), Some("loop+1".to_string()));
+
+        let listing = disasm::disassemble_with_symbols(&code, &symbols).to_string();
+        assert!(listing.contains("loop:\n    OUT [buf+1]"));
+        assert_eq!(assemble(&listing), Ok(code));
+    }
+
+    #[test]
+    fn reports_error_position() {
+        let err = assemble("start:\n    ADD #1, #2, #3").unwrap_err();
+        assert_eq!((err.line, err.col, err.kind), (2, 5, AsmErrorKind::ImmediateWrite));
+
+        let err = assemble("JNZ #1, nowhere").unwrap_err();
+        assert_eq!((err.line, err.col), (1, 9));
+        assert_eq!(err.kind, AsmErrorKind::UndefinedLabel("nowhere".to_string()));
+
+        let err = assemble("OUT [5").unwrap_err();
+        assert_eq!((err.line, err.col, err.kind), (1, 7, AsmErrorKind::UnexpectedEnd));
+
+        let err = assemble("db 1, 2x").unwrap_err();
+        assert_eq!((err.line, err.col), (1, 7));
+        assert_eq!(err.kind, AsmErrorKind::BadNumber("2x".to_string()));
+    }
+
+    #[test]
+    fn rejects_values_out_of_range() {
+        assert_eq!(assemble("db -9223372036854775808, 9223372036854775807"), Ok(vec![Word::MIN, Word::MAX]));
+
+        let err = assemble("db 9223372036854775808").unwrap_err();
+        assert_eq!((err.line, err.col, err.kind), (1, 4, AsmErrorKind::Overflow));
+
+        let err = assemble("OUT rb-0-9223372036854775807-1").unwrap_err();
+        assert_eq!((err.line, err.col, err.kind), (1, 8, AsmErrorKind::Overflow));
+
+        let err = assemble("db 0\nend: db end+9223372036854775807").unwrap_err();
+        assert_eq!((err.line, err.col, err.kind), (2, 9, AsmErrorKind::Overflow));
+
+        let err = assemble("db 0\n    ds 9223372036854775807").unwrap_err();
+        assert_eq!((err.line, err.col, err.kind), (2, 8, AsmErrorKind::TooLarge));
+    }
+}
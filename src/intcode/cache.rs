@@ -0,0 +1,166 @@
+use super::{OpCode, ExecResult};
+use super::word::IntcodeWord;
+use super::memory::{Memory, MemoryVersion};
+
+/// instructions at addresses past this are decoded every time they're executed, so that a
+/// program jumping far into memory can't make the cache huge
+const MAX_CACHED_ADDR: usize = 1 << 16;
+
+/// the decoded op and parameter modes of instructions that have already been executed, by
+/// address. parameters are still read from memory every time, so an entry only has to be thrown
+/// away when its opcode is written to
+pub(super) struct DecodeCache<W> {
+    /// the position in `entries` of the instruction at each address. programs only execute a
+    /// fraction of their words as instructions, so this is kept small
+    index: Vec<u32>,
+
+    /// each cached instruction and its address, in the order they were cached. positions in
+    /// `index` can be left pointing to entries for other addresses by `clear`, so the address is
+    /// checked on every lookup
+    entries: Vec<(usize, OpCode<W>)>,
+
+    /// the memory the cache was last revalidated against, and how many entries there were then.
+    /// any of those entries which are still cached haven't had their opcode written to since,
+    /// so restoring the same memory again only has to check the entries after them
+    synced: Option<MemoryVersion>,
+    checked: usize,
+
+    /// a bit for each address, modulo the number of bits, which is set once it's been executed
+    executed: [u64; EXECUTED_WORDS],
+}
+
+const NOT_CACHED: u32 = u32::MAX;
+
+const EXECUTED_WORDS: usize = 16;
+
+impl<W: IntcodeWord> DecodeCache<W> {
+    pub fn new() -> Self {
+        Self {
+            index: Vec::new(),
+            entries: Vec::new(),
+            synced: None,
+            checked: 0,
+            executed: [0; EXECUTED_WORDS],
+        }
+    }
+
+    /// the instruction at `pc`, decoding and caching it if it isn't cached yet
+    pub fn fetch(&mut self, mem: &Memory<W>, pc: usize) -> ExecResult<OpCode<W>, W> {
+        if let Some(pos) = self.index.get(pc) {
+            if let Some((addr, opcode)) = self.entries.get(*pos as usize) {
+                if *addr == pc {
+                    let mut opcode = opcode.clone();
+                    opcode.load_params(mem, pc);
+                    return Ok(opcode);
+                }
+            }
+        }
+
+        if pc >= MAX_CACHED_ADDR {
+            return OpCode::fetch(mem, pc);
+        }
+
+        // most instructions in short programs only run once, so they're only cached the second
+        // time. addresses share bits, which just means some get cached sooner
+        let (word, bit) = (pc / 64 % EXECUTED_WORDS, 1 << (pc % 64));
+        if self.executed[word] & bit == 0 {
+            self.executed[word] |= bit;
+            return OpCode::fetch(mem, pc);
+        }
+
+        let opcode = OpCode::fetch(mem, pc)?;
+
+        // invalidated entries are never reused, so a program which keeps modifying its code
+        // would fill the cache up
+        if self.entries.len() >= MAX_CACHED_ADDR {
+            self.clear();
+        }
+
+        if pc >= self.index.len() {
+            self.index.resize(pc + 1, NOT_CACHED);
+        }
+        self.index[pc] = self.entries.len() as u32;
+        self.entries.push((pc, opcode.clone()));
+
+        Ok(opcode)
+    }
+
+    /// throw away the instruction at `addr`, which is being written to
+    pub fn write(&mut self, addr: usize) {
+        if let Some(pos) = self.index.get_mut(addr) {
+            *pos = NOT_CACHED;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.checked = 0;
+    }
+
+    /// bring the cache up to date after memory has been replaced with `mem`
+    pub fn revalidate(&mut self, mem: &Memory<W>) {
+        if self.synced == Some(mem.version()) {
+            for pos in self.checked..self.entries.len() {
+                self.check(mem, self.entries[pos].0);
+            }
+        } else {
+            for pc in 0..self.index.len() {
+                self.check(mem, pc);
+            }
+        }
+
+        self.synced = Some(mem.version());
+        self.checked = self.entries.len();
+    }
+
+    /// throw away the instruction at `pc` if `mem` holds a different opcode there
+    fn check(&mut self, mem: &Memory<W>, pc: usize) {
+        if let Some(pos) = self.index.get_mut(pc) {
+            match self.entries.get(*pos as usize) {
+                Some((addr, opcode)) if *addr == pc && mem.load(pc) == opcode.word => {}
+                _ => *pos = NOT_CACHED,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::intcode::{Computer, from_str};
+
+    #[test]
+    fn runs_self_modifying_code() {
+        // adds 100 to the word at 0 after it's been executed, turning OUT #7 into OUT rb+7, then
+        // runs it again
+        let mut computer = Computer::new(from_str("104,7,1001,0,100,0,1005,30,16,1101,1,0,30,1105,1,0,99"));
+        computer.set_decode_cache(true);
+        let initial = computer.snapshot();
+        computer.run().unwrap();
+        assert_eq!(computer.out_buf, [7, 30]);
+
+        // restoring puts the original code back under the cached instructions
+        computer.restore(&initial);
+        computer.run().unwrap();
+        assert_eq!(computer.out_buf, [7, 30]);
+    }
+
+    #[test]
+    fn restores_different_code() {
+        // outputs its counter until it reaches 2, running the OUT at 4 twice so that it's cached
+        let mut computer = Computer::new(from_str("1001,14,1,14,4,14,1007,14,2,15,1005,15,0,99,0,0"));
+        let initial = computer.snapshot();
+        computer.run().unwrap();
+        assert_eq!(computer.out_buf, [1, 2]);
+
+        // the same snapshot with OUT [14] replaced by OUT #14
+        let mut changed = initial.clone();
+        changed.mem.store(4, 104);
+        computer.restore(&changed);
+        computer.run().unwrap();
+        assert_eq!(computer.out_buf, [14, 14]);
+
+        computer.restore(&initial);
+        computer.run().unwrap();
+        assert_eq!(computer.out_buf, [1, 2]);
+    }
+}
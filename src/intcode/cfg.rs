@@ -0,0 +1,318 @@
+//! static control flow analysis. instructions are found by following jumps from address 0, and
+//! split into basic blocks: runs of instructions which are only entered at the top and only left
+//! at the bottom. jumps with immediate targets are resolved; any other jump goes somewhere
+//! `Unknown`, since its target depends on memory at runtime
+
+use super::{Word, Op, Mode};
+use super::disasm::{decode_at, Instruction};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// whether a jump instruction can jump, and whether it can fall through to the next instruction,
+/// which isn't always the case when its condition is immediate
+fn branches(instruction: &Instruction) -> (bool, bool) {
+    let cond = &instruction.operands[0];
+    match cond.mode {
+        Mode::Immediate => {
+            let taken = (cond.value != 0) == (instruction.op == Op::Jnz);
+            (taken, !taken)
+        }
+        _ => (true, true),
+    }
+}
+
+/// the instructions reachable from address 0, by address, and the addresses that were only found
+/// as entry points. jumps with computed targets can't be followed, so immediate operands that
+/// point to the start of a valid instruction are also treated as entry points, which finds return
+/// addresses pushed before calls
+pub(super) fn find_instructions(code: &[Word]) -> (BTreeMap<usize, Instruction>, BTreeSet<usize>) {
+    let mut instructions: BTreeMap<usize, Instruction> = BTreeMap::new();
+    let mut entries = BTreeSet::new();
+    let mut pending = vec![0];
+
+    let overlaps = |instructions: &BTreeMap<usize, Instruction>, addr: usize, size: usize| {
+        let before = instructions.range(..addr).next_back()
+            .map(|(start, instruction)| start + instruction.size() > addr)
+            .unwrap_or(false);
+        before || instructions.range(addr..addr + size).next().is_some()
+    };
+
+    loop {
+        while let Some(addr) = pending.pop() {
+            if instructions.contains_key(&addr) {
+                continue;
+            }
+
+            let instruction = match decode_at(code, addr) {
+                Some(instruction) if !overlaps(&instructions, addr, instruction.size()) => instruction,
+                _ => continue,
+            };
+
+            let next = addr + instruction.size();
+            match instruction.op {
+                Op::Hcf => {}
+
+                Op::Jnz | Op::Jz => {
+                    let (jumps, falls_through) = branches(&instruction);
+                    if jumps {
+                        pending.extend(instruction.jump_target());
+                    }
+                    if falls_through {
+                        pending.push(next);
+                    }
+                }
+
+                _ => pending.push(next),
+            }
+
+            instructions.insert(addr, instruction);
+        }
+
+        // each round adds at least one instruction, since candidates must decode without
+        // overlapping anything found so far
+        pending = instructions.values()
+            .filter(|instruction| instruction.jump_target().is_none())
+            .flat_map(|instruction| instruction.operands.iter())
+            .filter(|operand| operand.mode == Mode::Immediate && operand.value >= 0)
+            .map(|operand| operand.value as usize)
+            .filter(|addr| !instructions.contains_key(addr))
+            .filter(|addr| match decode_at(code, *addr) {
+                Some(instruction) => !overlaps(&instructions, *addr, instruction.size()),
+                None => false,
+            })
+            .collect();
+
+        if pending.is_empty() {
+            entries.insert(0);
+            break (instructions, entries);
+        }
+
+        entries.extend(pending.iter().cloned());
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Target {
+    Addr(usize),
+
+    /// the target of a jump that isn't immediate
+    Unknown,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum EdgeKind {
+    Fallthrough,
+    Jump,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Edge {
+    pub kind: EdgeKind,
+    pub target: Target,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Block {
+    pub start: usize,
+
+    /// each instruction in the block and its address
+    pub instructions: Vec<(usize, Instruction)>,
+
+    /// where execution can go after the last instruction. empty if it halts
+    pub edges: Vec<Edge>,
+}
+
+impl Block {
+    /// the address after the last instruction in the block
+    pub fn end(&self) -> usize {
+        let (addr, last) = self.instructions.last().expect("blocks aren't empty");
+        addr + last.size()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Cfg {
+    /// blocks by start address
+    pub blocks: BTreeMap<usize, Block>,
+}
+
+/// find the basic blocks of a program and the edges between them
+pub fn build(code: &[Word]) -> Cfg {
+    let (instructions, entries) = find_instructions(code);
+
+    let is_jump = |instruction: &Instruction| matches!(instruction.op, Op::Jnz | Op::Jz | Op::Hcf);
+
+    // a block starts at each entry point and jump target, and wherever the previous word isn't
+    // the end of an instruction which falls through
+    let mut leaders: BTreeSet<usize> = entries;
+    leaders.extend(instructions.values().filter_map(Instruction::jump_target));
+    let mut prev_end = None;
+    for (addr, instruction) in &instructions {
+        if prev_end != Some(*addr) {
+            leaders.insert(*addr);
+        }
+
+        prev_end = if is_jump(instruction) { None } else { Some(addr + instruction.size()) };
+    }
+
+    let mut blocks = BTreeMap::new();
+    let mut current: Option<Block> = None;
+    for (addr, instruction) in &instructions {
+        let addr = *addr;
+        if leaders.contains(&addr) {
+            if let Some(block) = current.take() {
+                blocks.insert(block.start, block);
+            }
+        }
+
+        let next = addr + instruction.size();
+        let block = current.get_or_insert_with(|| Block {
+            start: addr,
+            instructions: Vec::new(),
+            edges: Vec::new(),
+        });
+
+        let edges = match instruction.op {
+            Op::Hcf => Some(Vec::new()),
+
+            Op::Jnz | Op::Jz => {
+                let (jumps, falls_through) = branches(instruction);
+                let mut edges = Vec::new();
+                if jumps {
+                    let target = instruction.jump_target().map(Target::Addr).unwrap_or(Target::Unknown);
+                    edges.push(Edge { kind: EdgeKind::Jump, target });
+                }
+                if falls_through {
+                    edges.push(Edge { kind: EdgeKind::Fallthrough, target: Target::Addr(next) });
+                }
+                Some(edges)
+            }
+
+            _ if leaders.contains(&next) || !instructions.contains_key(&next) => {
+                Some(vec![Edge { kind: EdgeKind::Fallthrough, target: Target::Addr(next) }])
+            }
+
+            _ => None,
+        };
+
+        block.instructions.push((addr, instruction.clone()));
+
+        if let Some(edges) = edges {
+            block.edges = edges;
+            let block = current.take().unwrap();
+            blocks.insert(block.start, block);
+        }
+    }
+
+    if let Some(block) = current {
+        blocks.insert(block.start, block);
+    }
+
+    Cfg {
+        blocks,
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Cfg {
+    /// the block containing the word at `addr`
+    pub fn block_at(&self, addr: usize) -> Option<&Block> {
+        self.blocks.range(..=addr).next_back()
+            .map(|(_, block)| block)
+            .filter(|block| addr < block.end())
+    }
+
+    /// a Graphviz graph with a node for each block, listing its instructions. jump edges are
+    /// labelled, and jumps to unknown targets go to a single `unknown` node. edges to addresses
+    /// which aren't the start of a block are drawn to a node for the address
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph intcode {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+
+        let mut missing = BTreeSet::new();
+        let mut unknown = false;
+
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for (addr, instruction) in &block.instructions {
+                label.push_str(&format!("{}: {}\\l", addr, dot_escape(&instruction.to_string())));
+            }
+            dot.push_str(&format!("    b{} [label=\"{}\"];\n", block.start, label));
+
+            for edge in &block.edges {
+                let target = match edge.target {
+                    Target::Addr(addr) => {
+                        if !self.blocks.contains_key(&addr) {
+                            missing.insert(addr);
+                        }
+                        format!("b{}", addr)
+                    }
+                    Target::Unknown => {
+                        unknown = true;
+                        "unknown".to_string()
+                    }
+                };
+
+                let style = match (edge.kind, edge.target) {
+                    (EdgeKind::Jump, Target::Unknown) => " [label=\"jump\", style=dashed]",
+                    (EdgeKind::Jump, _) => " [label=\"jump\"]",
+                    (EdgeKind::Fallthrough, _) => "",
+                };
+                dot.push_str(&format!("    b{} -> {}{};\n", block.start, target, style));
+            }
+        }
+
+        for addr in missing {
+            dot.push_str(&format!("    b{} [label=\"{}: ?\", style=dashed];\n", addr, addr));
+        }
+        if unknown {
+            dot.push_str("    unknown [label=\"?\", shape=diamond, style=dashed];\n");
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    #[test]
+    fn splits_blocks_at_jumps() {
+        let code = assemble("
+                    IN [x]
+                    JZ [x], skip
+                    OUT [x]
+            skip:   ADD #ret, #0, rb+0
+                    JZ #0, sub
+            ret:    HALT
+            sub:    OUT #1
+                    JZ #0, rb+0
+            x:      db 0
+        ").unwrap();
+        let cfg = build(&code);
+
+        let starts: Vec<_> = cfg.blocks.keys().cloned().collect();
+        assert_eq!(starts, [0, 5, 7, 14, 15]);
+
+        let jump = |addr| Edge { kind: EdgeKind::Jump, target: Target::Addr(addr) };
+        let fallthrough = |addr| Edge { kind: EdgeKind::Fallthrough, target: Target::Addr(addr) };
+        assert_eq!(cfg.blocks[&0].edges, [jump(7), fallthrough(5)]);
+        assert_eq!(cfg.blocks[&5].edges, [fallthrough(7)]);
+        assert_eq!(cfg.blocks[&7].edges, [jump(15)]);
+        assert_eq!(cfg.blocks[&14].edges, []);
+        assert_eq!(cfg.blocks[&15].edges, [Edge { kind: EdgeKind::Jump, target: Target::Unknown }]);
+
+        assert_eq!(cfg.block_at(13).map(|block| block.start), Some(7));
+        assert_eq!(cfg.block_at(20), None);
+
+        let dot = cfg.to_dot();
+        assert!(dot.contains("    b0 [label=\"0: IN [20]\\l2: JZ [20], #7\\l\"];\n"));
+        assert!(dot.contains("    b0 -> b7 [label=\"jump\"];\n"));
+        assert!(dot.contains("    b15 -> unknown [label=\"jump\", style=dashed];\n"));
+    }
+}
@@ -0,0 +1,203 @@
+use super::Computer;
+use super::memory::Memory;
+use super::word::IntcodeWord;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// states are forgotten once this many have been seen without finding a loop, so loops which
+/// take longer than this many backward jumps to repeat might not be found
+const MAX_SEEN_STATES: usize = 1 << 20;
+
+/// a state that was seen twice, which is checked by running the same number of steps again and
+/// comparing the whole state, in case the match was a hash collision
+struct Check<W> {
+    pc: usize,
+    rel_offset: W,
+    mem: Memory<W>,
+    at_step: u64,
+
+    /// the addresses of the instructions executed since the check started
+    start: usize,
+    end: usize,
+}
+
+/// finds loops a program can never leave, by looking for a repeat of the whole machine state at
+/// the targets of backward jumps, which every loop has to make. a repeated state only proves the
+/// program is stuck if it didn't read any input in between, so reading input starts over
+pub(super) struct CycleDetector<W> {
+    /// a hash of the contents of memory, updated as it's written to
+    digest: u64,
+    steps: u64,
+    seen: HashMap<u64, u64>,
+    check: Option<Check<W>>,
+}
+
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// what the word at `addr` adds to the memory digest. zeros add nothing, so pages which haven't
+/// been written to don't need to be visited
+fn word_digest<W: IntcodeWord>(addr: usize, word: &W) -> u64 {
+    if word.is_zero() {
+        return 0;
+    }
+
+    let bits = match word.to_i64() {
+        Some(word) => word as u64,
+        None => {
+            let mut hasher = DefaultHasher::new();
+            word.hash(&mut hasher);
+            hasher.finish()
+        }
+    };
+    mix(mix(addr as u64) ^ bits)
+}
+
+impl<W: IntcodeWord> CycleDetector<W> {
+    pub fn new(mem: &Memory<W>) -> Self {
+        let digest = mem.pages()
+            .flat_map(|(start, page)| page.iter().enumerate().map(move |(i, word)| (start + i, word)))
+            .fold(0, |digest, (addr, word)| digest ^ word_digest(addr, word));
+
+        Self {
+            digest,
+            steps: 0,
+            seen: HashMap::new(),
+            check: None,
+        }
+    }
+
+    pub fn write(&mut self, addr: usize, old: &W, new: &W) {
+        self.digest ^= word_digest(addr, old) ^ word_digest(addr, new);
+    }
+
+    pub fn input_read(&mut self) {
+        self.seen.clear();
+        self.check = None;
+    }
+
+    /// record that the instruction at `prev_pc` has executed, leaving the pc at `pc`. returns
+    /// the range of instruction addresses in the loop if it's now proven the program can't leave it
+    pub fn step(
+        &mut self,
+        prev_pc: usize,
+        jumped: bool,
+        pc: usize,
+        rel_offset: &W,
+        mem: &Memory<W>
+    ) -> Option<(usize, usize)> {
+        self.steps += 1;
+
+        if let Some(check) = &mut self.check {
+            check.start = check.start.min(prev_pc);
+            check.end = check.end.max(prev_pc);
+        }
+
+        // the state can only repeat after a backward jump
+        if !jumped || pc > prev_pc {
+            return None;
+        }
+
+        if let Some(check) = &self.check {
+            if self.steps == check.at_step
+                && pc == check.pc
+                && *rel_offset == check.rel_offset
+                && *mem == check.mem
+            {
+                return Some((check.start, check.end));
+            }
+
+            if self.steps >= check.at_step {
+                self.check = None;
+            }
+        }
+
+        let mut hasher = DefaultHasher::new();
+        (pc, rel_offset, self.digest).hash(&mut hasher);
+
+        if self.seen.len() >= MAX_SEEN_STATES {
+            self.seen.clear();
+        }
+
+        match self.seen.insert(hasher.finish(), self.steps) {
+            Some(seen_at) if self.check.is_none() => {
+                self.check = Some(Check {
+                    pc,
+                    rel_offset: rel_offset.clone(),
+                    mem: mem.clone(),
+                    at_step: self.steps + (self.steps - seen_at),
+                    start: usize::MAX,
+                    end: 0,
+                });
+            }
+            _ => {}
+        }
+
+        None
+    }
+}
+
+impl<W: IntcodeWord> Computer<W> {
+    /// check for loops that the program can never leave while running, which are reported as
+    /// `ExecError::InfiniteLoop`. this makes execution slower, since the state of memory has to be
+    /// tracked
+    pub fn set_loop_detection(&mut self, enabled: bool) {
+        self.cycles = if enabled { Some(CycleDetector::new(&self.mem)) } else { None };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::intcode::{Computer, ExecError, asm::assemble};
+
+    #[test]
+    fn detects_stuck_loop() {
+        let code = assemble("
+            count:  ADD [n], #1, [n]
+                    LT [n], #3, [more]
+                    JNZ [more], count
+            spin:   ADD [x], #1, [x]
+                    MUL [x], #0, [x]
+                    JZ #0, spin
+                    HALT
+            n:      db 0
+            more:   db 0
+            x:      db 0
+        ").unwrap();
+
+        let mut computer = Computer::new(code);
+        computer.set_loop_detection(true);
+        assert_eq!(computer.run(), Err(ExecError::InfiniteLoop { start: 11, end: 19 }));
+        assert_eq!(computer.mem_load(23), 3);
+    }
+
+    #[test]
+    fn counting_loop_is_not_stuck() {
+        let mut computer = Computer::new(assemble("loop: ADD [n], #1, [n]\nJZ #0, loop\nn: db 0").unwrap());
+        computer.set_loop_detection(true);
+        computer.set_step_budget(Some(10_000));
+
+        assert_eq!(computer.run(), Err(ExecError::StepBudgetExceeded));
+        assert_eq!(computer.mem_load(7), 5_000);
+
+        // running out of budget leaves the computer ready to carry on
+        computer.set_step_budget(Some(2));
+        assert_eq!(computer.run(), Err(ExecError::StepBudgetExceeded));
+        assert_eq!(computer.mem_load(7), 5_001);
+        assert_eq!(computer.step_budget(), Some(0));
+    }
+
+    #[test]
+    fn loop_reading_input_is_not_stuck() {
+        let mut computer = Computer::new(assemble("loop: IN [x]\nJZ [x], loop\nOUT [x]\nHALT\nx: db 0").unwrap());
+        computer.set_loop_detection(true);
+        computer.in_buf.extend(&[0, 0, 0, 5]);
+
+        assert_eq!(computer.run(), Ok(()));
+        assert_eq!(computer.out_buf, [5]);
+    }
+}
@@ -0,0 +1,256 @@
+use super::{Word, Op, Mode, OpCode};
+use super::symbols::SymbolTable;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Operand {
+    pub mode: Mode,
+    pub value: Word,
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            Mode::Pointer => write!(f, "[{}]", self.value),
+            Mode::Immediate => write!(f, "#{}", self.value),
+            Mode::Relative if self.value < 0 => write!(f, "rb{}", self.value),
+            Mode::Relative => write!(f, "rb+{}", self.value),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Instruction {
+    pub op: Op,
+    pub operands: Vec<Operand>,
+}
+
+impl Instruction {
+    pub fn size(&self) -> usize {
+        1 + self.operands.len()
+    }
+
+    /// the address this instruction jumps to, if it's a jump with an immediate target
+    pub fn jump_target(&self) -> Option<usize> {
+        match self.op {
+            Op::Jz | Op::Jnz if self.operands[1].mode == Mode::Immediate => {
+                let target = self.operands[1].value;
+                if target >= 0 {
+                    Some(target as usize)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.op.mnemonic())?;
+
+        for (i, operand) in self.operands.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, operand)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// decode the instruction starting at `addr`. returns `None` if the word there isn't a valid
+/// opcode, if it has mode digits for parameters the opcode doesn't take, if the program ends
+/// before all its operands, or if it writes to an immediate operand
+pub fn decode_at(code: &[Word], addr: usize) -> Option<Instruction> {
+    let word = code.get(addr)?;
+    let opcode = OpCode::decode(addr, word).ok()?;
+    let param_count = opcode.op.param_count();
+    if addr + param_count >= code.len() {
+        return None;
+    }
+
+    // the interpreter ignores extra mode digits, but the assembler can't produce them
+    if OpCode::encode(opcode.op, &opcode.param_modes[..param_count]) != *word {
+        return None;
+    }
+
+    if let Some(out_param) = opcode.op.out_param() {
+        if opcode.param_mode(out_param) == Mode::Immediate {
+            return None;
+        }
+    }
+
+    let operands = (0..param_count)
+        .map(|param| Operand {
+            mode: opcode.param_mode(param),
+            value: code[addr + 1 + param],
+        })
+        .collect();
+
+    Some(Instruction {
+        op: opcode.op,
+        operands,
+    })
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Item {
+    Instruction(Instruction),
+    Data(Word),
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Line {
+    pub addr: usize,
+    pub words: Vec<Word>,
+    pub item: Item,
+}
+
+/// a disassembled program. its `Display` output is valid source for the assembler, with each
+/// line's address and raw words in a trailing comment
+#[derive(Clone, Debug)]
+pub struct Listing {
+    pub lines: Vec<Line>,
+    pub labels: BTreeMap<usize, String>,
+
+    /// used to name addresses inside variables, like `[buf+3]`
+    pub symbols: SymbolTable,
+}
+
+impl Listing {
+    /// the label for `addr`, or the variable it's in if that's labelled in the listing
+    fn addr_name(&self, addr: Word) -> Option<String> {
+        if addr < 0 {
+            return None;
+        }
+        if let Some(label) = self.labels.get(&(addr as usize)) {
+            return Some(label.clone());
+        }
+
+        let (symbol, offset) = self.symbols.lookup(addr as usize)?;
+        match self.labels.get(&symbol.addr) {
+            Some(label) if *label == symbol.name => Some(format!("{}+{}", symbol.name, offset)),
+            _ => None,
+        }
+    }
+
+    fn instruction_text(&self, instruction: &Instruction) -> String {
+        let mut text = instruction.op.mnemonic().to_string();
+
+        for (i, operand) in instruction.operands.iter().enumerate() {
+            text.push_str(if i == 0 { " " } else { ", " });
+
+            let is_target = i == 1 && instruction.jump_target().is_some();
+            match self.addr_name(operand.value) {
+                Some(name) if is_target => text.push_str(&name),
+                Some(name) if operand.mode == Mode::Pointer => text.push_str(&format!("[{}]", name)),
+                _ => text.push_str(&operand.to_string()),
+            }
+        }
+
+        text
+    }
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            if let Some(label) = self.labels.get(&line.addr) {
+                writeln!(f, "{}:", label)?;
+            }
+
+            let text = match &line.item {
+                Item::Instruction(instruction) => self.instruction_text(instruction),
+                Item::Data(word) => format!("db {}", word),
+            };
+
+            let words: Vec<_> = line.words.iter().map(Word::to_string).collect();
+            writeln!(f, "    {:<32} ; {}: {}", text, line.addr, words.join(","))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// disassemble a whole program by decoding it from start to end. any word that can't be decoded
+/// as an instruction is shown as data and decoding continues from the word after it
+pub fn disassemble(code: &[Word]) -> Listing {
+    let mut lines = Vec::new();
+    let mut addr = 0;
+
+    while addr < code.len() {
+        let item = match decode_at(code, addr) {
+            Some(instruction) => Item::Instruction(instruction),
+            None => Item::Data(code[addr]),
+        };
+
+        let len = match &item {
+            Item::Instruction(instruction) => instruction.size(),
+            Item::Data(_) => 1,
+        };
+
+        lines.push(Line {
+            addr,
+            words: code[addr..addr + len].to_vec(),
+            item,
+        });
+        addr += len;
+    }
+
+    let line_starts: HashSet<_> = lines.iter().map(|line| line.addr).collect();
+    let labels = lines.iter()
+        .filter_map(|line| match &line.item {
+            Item::Instruction(instruction) => instruction.jump_target(),
+            Item::Data(_) => None,
+        })
+        .filter(|target| line_starts.contains(target))
+        .map(|target| (target, format!("L{}", target)))
+        .collect();
+
+    Listing {
+        lines,
+        labels,
+        symbols: SymbolTable::new(),
+    }
+}
+
+/// disassemble a program, labelling lines with the names in `symbols` and using them for the
+/// addresses operands refer to
+pub fn disassemble_with_symbols(code: &[Word], symbols: &SymbolTable) -> Listing {
+    let mut listing = disassemble(code);
+
+    let line_starts: HashSet<_> = listing.lines.iter().map(|line| line.addr).collect();
+    for symbol in symbols.symbols() {
+        if line_starts.contains(&symbol.addr) {
+            listing.labels.insert(symbol.addr, symbol.name.clone());
+        }
+    }
+
+    listing.symbols = symbols.clone();
+    listing
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::from_str;
+
+    #[test]
+    fn disassembles_with_labels() {
+        let code = from_str("3,9,1005,9,7,99,42,204,-1,0");
+        let listing = disassemble(&code);
+        let text = listing.to_string();
+        let text: Vec<_> = text.lines().map(str::trim_end).collect();
+
+        assert_eq!(text, [
+            "    IN [9]                           ; 0: 3,9",
+            "    JNZ [9], L7                      ; 2: 1005,9,7",
+            "    HALT                             ; 5: 99",
+            "    db 42                            ; 6: 42",
+            "L7:",
+            "    OUT rb-1                         ; 7: 204,-1",
+            "    db 0                             ; 9: 0",
+        ]);
+    }
+}
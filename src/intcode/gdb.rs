@@ -0,0 +1,464 @@
+//! a stub for the GDB remote serial protocol, so that a debugger front-end can control a computer
+//! over a socket.
+//!
+//! the debugger sees memory as bytes, with each word stored as 8 little-endian bytes, so word `n`
+//! is at address `8 * n`. there are two 64-bit registers: `pc`, which is a byte address like
+//! the others, and `rb`, the relative base. `monitor input <val>...` pushes values to the
+//! computer's input, and output is printed to the debugger's console when execution stops
+
+use super::{Word, Computer, ExecError, StopReason};
+use std::io::{self, Read, Write};
+
+const WORD_BYTES: u64 = 8;
+
+/// the largest packet the debugger is allowed to send
+const PACKET_SIZE: usize = 0x4000;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.intcode.core">
+    <reg name="pc" bitsize="64" type="code_ptr"/>
+    <reg name="rb" bitsize="64" type="int64"/>
+  </feature>
+</target>
+"#;
+
+/// stop replies, with the number gdb uses for the signal
+const SIGILL: &str = "S04";
+const SIGTRAP: &str = "S05";
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 == 1 {
+        return None;
+    }
+
+    (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_addr(hex: &str) -> Option<u64> {
+    u64::from_str_radix(hex, 16).ok()
+}
+
+/// parse `addr,len`, both in hex
+fn parse_range(args: &str) -> Option<(u64, u64)> {
+    let mut parts = args.splitn(2, ',');
+    let addr = parse_addr(parts.next()?)?;
+    let len = parse_addr(parts.next()?)?;
+    Some((addr, len))
+}
+
+fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0, |sum, b| sum.wrapping_add(b))
+}
+
+struct Session<'a, S> {
+    computer: &'a mut Computer,
+    stream: S,
+
+    /// whether packets are acknowledged, until the debugger turns it off with `QStartNoAckMode`
+    acks: bool,
+    last_reply: String,
+}
+
+impl<'a, S: Read + Write> Session<'a, S> {
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        match self.stream.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    /// the data of the next packet with a good checksum, or `None` if the connection was closed
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(b'$') => {}
+                Some(b'-') => {
+                    let reply = self.last_reply.clone();
+                    self.write_packet(&reply)?;
+                    continue;
+                }
+
+                // acks for our replies, and interrupts, which can't arrive while the computer is
+                // running because it runs on this thread
+                Some(_) => continue,
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(b) if data.len() < PACKET_SIZE => data.push(b),
+                    Some(_) => {}
+                }
+            }
+
+            let mut sum = [0; 2];
+            for digit in &mut sum {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b) => *digit = b,
+                }
+            }
+
+            let data = String::from_utf8_lossy(&data).into_owned();
+            let sum = std::str::from_utf8(&sum).ok().and_then(|sum| u8::from_str_radix(sum, 16).ok());
+            if sum == Some(checksum(&data)) {
+                if self.acks {
+                    self.stream.write_all(b"+")?;
+                }
+                return Ok(Some(data));
+            } else if self.acks {
+                self.stream.write_all(b"-")?;
+            }
+        }
+    }
+
+    fn write_packet(&mut self, data: &str) -> io::Result<()> {
+        write!(self.stream, "${}#{:02x}", data, checksum(data))?;
+        self.stream.flush()
+    }
+
+    fn reply(&mut self, data: &str) -> io::Result<()> {
+        self.write_packet(data)?;
+        self.last_reply = data.to_string();
+        Ok(())
+    }
+
+    /// print to the debugger's console
+    fn console(&mut self, text: &str) -> io::Result<()> {
+        self.write_packet(&format!("O{}", to_hex(text.as_bytes())))
+    }
+
+    /// none if the pc is too large to be a byte address
+    fn registers(&self) -> Option<[u64; 2]> {
+        let pc = (self.computer.pc() as u64).checked_mul(WORD_BYTES)?;
+        Some([pc, self.computer.rel_offset() as u64])
+    }
+
+    fn read_mem(&self, addr: u64, len: u64) -> Vec<u8> {
+        (addr..addr.saturating_add(len)).map(|addr| {
+            let word = self.computer.mem_load((addr / WORD_BYTES) as usize);
+            word.to_le_bytes()[(addr % WORD_BYTES) as usize]
+        }).collect()
+    }
+
+    /// returns false without writing anything if the bytes would run past the last address
+    fn write_mem(&mut self, addr: u64, bytes: &[u8]) -> bool {
+        let end = match addr.checked_add(bytes.len() as u64) {
+            Some(end) => end,
+            None => return false,
+        };
+
+        for (addr, byte) in (addr..end).zip(bytes) {
+            let word_addr = (addr / WORD_BYTES) as usize;
+            let mut word = self.computer.mem_load(word_addr).to_le_bytes();
+            word[(addr % WORD_BYTES) as usize] = *byte;
+            self.computer.mem_store(word_addr, Word::from_le_bytes(word));
+        }
+        true
+    }
+
+    /// run until the computer stops for a reason other than producing output, or for one
+    /// instruction if `step` is set, then send the stop reply
+    fn resume(&mut self, step: bool) -> io::Result<()> {
+        let limit = if step { Some(1) } else { None };
+        let result = loop {
+            match self.computer.run_until(limit) {
+                Ok(StopReason::OutputProduced) if !step => continue,
+                result => break result,
+            }
+        };
+
+        let output: String = self.computer.out_buf.drain(..)
+            .map(|val| format!("output: {}\n", val))
+            .collect();
+        if !output.is_empty() {
+            self.console(&output)?;
+        }
+
+        match result {
+            Ok(StopReason::Halted) => self.reply("W00"),
+            Ok(StopReason::NeedsInput) => {
+                self.console("waiting for input\n")?;
+                self.reply(SIGTRAP)
+            }
+            Ok(StopReason::WatchpointHit(hit)) => {
+                self.console(&format!("{}\n", hit))?;
+                self.reply(SIGTRAP)
+            }
+            Ok(_) => self.reply(SIGTRAP),
+            Err(err) => {
+                self.console(&format!("stopped: {}\n", err))?;
+                self.reply(SIGILL)
+            }
+        }
+    }
+
+    fn monitor(&mut self, cmd: &str) -> io::Result<()> {
+        let mut args = cmd.split_whitespace();
+        match args.next() {
+            Some("input") => {
+                let vals: Result<Vec<Word>, _> = args.map(str::parse).collect();
+                match vals {
+                    Ok(vals) => {
+                        self.computer.in_buf.extend(vals);
+                        self.reply("OK")
+                    }
+                    Err(_) => self.reply("E01"),
+                }
+            }
+            _ => {
+                self.console("commands:\n    input <val>...    push values to the input buffer\n")?;
+                self.reply("OK")
+            }
+        }
+    }
+
+    fn set_breakpoint(&mut self, args: &str, add: bool) -> io::Result<()> {
+        // software and hardware breakpoints work the same way
+        let mut parts = args.splitn(3, ',');
+        let (kind, addr) = (parts.next(), parts.next().and_then(parse_addr));
+        match (kind, addr) {
+            (Some("0"), Some(addr)) | (Some("1"), Some(addr)) => {
+                let addr = (addr / WORD_BYTES) as usize;
+                if add {
+                    self.computer.add_breakpoint(addr);
+                } else {
+                    self.computer.remove_breakpoint(addr);
+                }
+                self.reply("OK")
+            }
+            (_, Some(_)) => self.reply(""),
+            _ => self.reply("E01"),
+        }
+    }
+
+    /// handle a packet, returning false if the session is over
+    fn handle(&mut self, packet: &str) -> io::Result<bool> {
+        let (cmd, args) = packet.split_at(packet.chars().next().map(char::len_utf8).unwrap_or(0));
+
+        match cmd {
+            "?" => self.reply(SIGTRAP)?,
+            "g" => match self.registers() {
+                Some(regs) => {
+                    let regs: Vec<u8> = regs.iter().flat_map(|reg| reg.to_le_bytes()).collect();
+                    self.reply(&to_hex(&regs))?;
+                }
+                None => self.reply("E01")?,
+            },
+            "p" => {
+                let index = usize::from_str_radix(args, 16).ok();
+                match index.zip(self.registers()).and_then(|(i, regs)| regs.get(i).cloned()) {
+                    Some(reg) => self.reply(&to_hex(&reg.to_le_bytes()))?,
+                    None => self.reply("E01")?,
+                }
+            }
+            "m" => match parse_range(args) {
+                Some((addr, len)) if len as usize <= PACKET_SIZE / 2 => {
+                    let bytes = self.read_mem(addr, len);
+                    self.reply(&to_hex(&bytes))?;
+                }
+                _ => self.reply("E01")?,
+            },
+            "M" => {
+                let mut parts = args.splitn(2, ':');
+                let range = parts.next().and_then(parse_range);
+                match (range, parts.next().and_then(from_hex)) {
+                    (Some((addr, len)), Some(bytes)) if len == bytes.len() as u64 => {
+                        if self.write_mem(addr, &bytes) {
+                            self.reply("OK")?;
+                        } else {
+                            self.reply("E01")?;
+                        }
+                    }
+                    _ => self.reply("E01")?,
+                }
+            }
+            "Z" => self.set_breakpoint(args, true)?,
+            "z" => self.set_breakpoint(args, false)?,
+            "c" => self.resume(false)?,
+            "s" => self.resume(true)?,
+            "H" => self.reply("OK")?,
+            "D" => {
+                self.reply("OK")?;
+                return Ok(false);
+            }
+            "k" => return Ok(false),
+            _ => self.query(packet)?,
+        }
+
+        Ok(true)
+    }
+
+    fn query(&mut self, packet: &str) -> io::Result<()> {
+        if packet.starts_with("qSupported") {
+            self.reply(&format!("PacketSize={:x};qXfer:features:read+;QStartNoAckMode+", PACKET_SIZE))
+        } else if packet == "QStartNoAckMode" {
+            self.reply("OK")?;
+            self.acks = false;
+            Ok(())
+        } else if packet == "qAttached" {
+            self.reply("1")
+        } else if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            match parse_range(range) {
+                Some((offset, len)) => {
+                    let start = (offset as usize).min(TARGET_XML.len());
+                    let end = start.saturating_add(len as usize).min(TARGET_XML.len());
+                    let more = if end < TARGET_XML.len() { "m" } else { "l" };
+                    self.reply(&format!("{}{}", more, &TARGET_XML[start..end]))
+                }
+                None => self.reply("E01"),
+            }
+        } else if let Some(cmd) = packet.strip_prefix("qRcmd,") {
+            match from_hex(cmd).and_then(|cmd| String::from_utf8(cmd).ok()) {
+                Some(cmd) => self.monitor(&cmd),
+                None => self.reply("E01"),
+            }
+        } else {
+            // an empty reply means the packet isn't supported
+            self.reply("")
+        }
+    }
+}
+
+/// serve a debugger connected to `stream`, until it detaches, kills the program or disconnects.
+/// errors executing the program are reported to the debugger, so only i/o errors are returned
+pub fn serve(computer: &mut Computer, stream: impl Read + Write) -> io::Result<()> {
+    let mut session = Session {
+        computer,
+        stream,
+        acks: true,
+        last_reply: String::new(),
+    };
+
+    while let Some(packet) = session.read_packet()? {
+        if !session.handle(&packet)? {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode;
+    use std::io::BufReader;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    struct Client {
+        stream: TcpStream,
+        reader: BufReader<TcpStream>,
+        console: String,
+    }
+
+    impl Client {
+        fn read_packet(&mut self) -> String {
+            let mut bytes = self.reader.by_ref().bytes().map(Result::unwrap);
+            assert_eq!(bytes.find(|b| *b != b'+'), Some(b'$'));
+
+            let data: Vec<u8> = bytes.by_ref().take_while(|b| *b != b'#').collect();
+            let data = String::from_utf8(data).unwrap();
+            let sum: String = bytes.take(2).map(char::from).collect();
+            assert_eq!(sum, format!("{:02x}", checksum(&data)));
+
+            self.stream.write_all(b"+").unwrap();
+            data
+        }
+
+        /// send a packet, and return the reply, collecting any console output before it
+        fn send(&mut self, data: &str) -> String {
+            write!(self.stream, "${}#{:02x}", data, checksum(data)).unwrap();
+
+            loop {
+                let reply = self.read_packet();
+                match reply.strip_prefix('O') {
+                    Some(hex) if reply != "OK" => {
+                        self.console.push_str(&String::from_utf8(from_hex(hex).unwrap()).unwrap());
+                    }
+                    _ => break reply,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn debugs_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let mut computer = Computer::new(intcode::from_str("3,11,1001,11,5,11,4,11,1105,1,0,0"));
+            let (stream, _) = listener.accept().unwrap();
+            stream.set_nodelay(true).unwrap();
+            serve(&mut computer, stream).unwrap();
+            computer
+        });
+
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_nodelay(true).unwrap();
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        let mut client = Client { stream, reader, console: String::new() };
+        assert!(client.send("qSupported:multiprocess+").contains("qXfer:features:read+"));
+        assert_eq!(client.send("?"), "S05");
+        assert_eq!(client.send("g"), "00000000000000000000000000000000");
+        assert!(client.send("qXfer:features:read:target.xml:0,1000").starts_with("l<?xml"));
+
+        // break before the OUT at word 6
+        assert_eq!(client.send("Z0,30,1"), "OK");
+        assert_eq!(client.send("c"), "S05");
+        assert_eq!(client.console, "waiting for input\n");
+
+        assert_eq!(client.send(&format!("qRcmd,{}", to_hex(b"input 10"))), "OK");
+        assert_eq!(client.send("c"), "S05");
+        assert_eq!(client.send("p0"), "3000000000000000");
+
+        // word 11 holds 15, which is replaced with 20
+        assert_eq!(client.send("m58,8"), "0f00000000000000");
+        assert_eq!(client.send("M58,1:14"), "OK");
+
+        client.console.clear();
+        assert_eq!(client.send("s"), "S05");
+        assert_eq!(client.console, "output: 20\n");
+        assert_eq!(client.send("p0"), "4000000000000000");
+
+        assert_eq!(client.send("z0,30,1"), "OK");
+        assert_eq!(client.send("c"), "S05");
+        assert_eq!(client.send("p0"), "0000000000000000");
+
+        // a breakpoint right after an output still stops execution
+        client.console.clear();
+        assert_eq!(client.send("Z0,40,1"), "OK");
+        assert_eq!(client.send(&format!("qRcmd,{}", to_hex(b"input 1"))), "OK");
+        assert_eq!(client.send("c"), "S05");
+        assert_eq!(client.console, "output: 6\n");
+        assert_eq!(client.send("p0"), "4000000000000000");
+        assert_eq!(client.send("z0,40,1"), "OK");
+
+        // jumping to word 2^61 leaves a pc with no byte address
+        assert_eq!(client.send("M50,8:0000000000000020"), "OK");
+        assert_eq!(client.send("s"), "S05");
+        assert_eq!(client.send("g"), "E01");
+        assert_eq!(client.send("p0"), "E01");
+
+        assert_eq!(client.send("Mfffffffffffffffe,4:01020304"), "E01");
+        assert_eq!(client.send("D"), "OK");
+
+        let computer = server.join().unwrap();
+        assert_eq!(computer.mem_load(11), 6);
+        assert_eq!(computer.breakpoints().count(), 0);
+    }
+}
@@ -0,0 +1,157 @@
+use super::{Word, Computer};
+use super::trace::TraceEvent;
+use super::word::IntcodeWord;
+use std::collections::VecDeque;
+
+struct Entry<W> {
+    rel_offset: W,
+    event: TraceEvent<W>,
+}
+
+/// undo log of the instructions a computer has executed while recording
+pub struct History<W = Word> {
+    entries: VecDeque<Entry<W>>,
+    limit: Option<usize>,
+}
+
+impl<W> History<W> {
+    pub(super) fn new(limit: Option<usize>) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            limit,
+        }
+    }
+
+    /// `rel_offset` is the relative base before `event` was executed
+    pub(super) fn push(&mut self, rel_offset: W, event: TraceEvent<W>) {
+        // with a limit of zero there's nothing to drop, and nothing should be kept
+        if self.limit == Some(self.entries.len()) && self.entries.pop_front().is_none() {
+            return;
+        }
+
+        self.entries.push_back(Entry { rel_offset, event });
+    }
+
+    pub(super) fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// the most recently executed instructions, oldest first
+    pub fn events(&self) -> impl Iterator<Item=&TraceEvent<W>> {
+        self.entries.iter().map(|entry| &entry.event)
+    }
+}
+
+impl<W: IntcodeWord> Computer<W> {
+    /// start keeping an undo log of every instruction executed, so that execution can be
+    /// reversed with `step_back`. if `limit` is set, only that many of the most recent
+    /// instructions can be undone
+    pub fn start_recording(&mut self, limit: Option<usize>) {
+        self.history = Some(History::new(limit));
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.history = None;
+    }
+
+    pub fn history(&self) -> Option<&History<W>> {
+        self.history.as_ref()
+    }
+
+    /// undo the last recorded instruction, restoring the memory it wrote to, the pc and the
+    /// relative base. input it read is pushed back onto the front of `in_buf`, and output it
+    /// wrote is removed from the end of `out_buf` if it's still there. returns false if there
+    /// was nothing to undo
+    pub fn step_back(&mut self) -> bool {
+        let entry = match self.history.as_mut().and_then(|history| history.entries.pop_back()) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        for write in entry.event.writes.into_iter().rev() {
+            self.mem_store(write.addr, write.old);
+        }
+
+        self.pc = entry.event.pc;
+        self.rel_offset = entry.rel_offset;
+
+        if let Some(input) = entry.event.input {
+            self.in_buf.push_front(input);
+        }
+
+        if let Some(output) = entry.event.output {
+            if self.out_buf.back() == Some(&output) {
+                self.out_buf.pop_back();
+            }
+        }
+
+        true
+    }
+
+    /// step back until the pc is `addr`, or until there's nothing left to undo. returns true if
+    /// execution was rewound to `addr`
+    pub fn run_back_until(&mut self, addr: usize) -> bool {
+        while self.step_back() {
+            if self.pc == addr {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::intcode::{Computer, from_str};
+
+    #[test]
+    fn steps_back_to_earlier_state() {
+        // reads two values and outputs their sum, twice, then halts
+        let code = "109,5,3,40,3,41,1,40,41,42,4,42,1001,43,1,43,1008,43,2,44,1006,44,2,99";
+        let mut computer = Computer::new(from_str(code));
+        computer.start_recording(None);
+        computer.in_buf.extend(&[1, 2, 3, 4]);
+        computer.run().unwrap();
+        assert_eq!(computer.out_buf, [3, 7]);
+
+        assert!(computer.run_back_until(10));
+        assert_eq!(computer.out_buf, [3]);
+        assert_eq!(computer.mem_load(42), 7);
+
+        assert!(computer.run_back_until(2));
+        assert_eq!(computer.in_buf, [3, 4]);
+        assert_eq!(computer.mem_load(43), 1);
+
+        while computer.step_back() {}
+        assert_eq!(computer.pc(), 0);
+        assert_eq!(computer.rel_offset(), 0);
+        assert_eq!(computer.in_buf, [1, 2, 3, 4]);
+        assert!(computer.out_buf.is_empty());
+        assert_eq!(computer.mem_load(42), 0);
+
+        computer.run().unwrap();
+        assert_eq!(computer.out_buf, [3, 7]);
+    }
+
+    #[test]
+    fn limited_history_drops_oldest() {
+        let mut computer = Computer::new(from_str("1101,1,1,20,1101,2,2,21,1101,3,3,22,99"));
+        computer.start_recording(Some(2));
+        computer.run().unwrap();
+
+        assert_eq!(computer.history().map(|history| history.len()), Some(2));
+        assert!(!computer.run_back_until(0));
+        assert_eq!(computer.pc(), 8);
+        assert_eq!(computer.mem_load(21), 4);
+        assert_eq!(computer.mem_load(22), 0);
+    }
+}
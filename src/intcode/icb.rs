@@ -0,0 +1,464 @@
+//! a compact binary format for programs and memory images, as an alternative to the text formats.
+//!
+//! a file starts with the magic bytes `\x7fICB`, a version byte and a flags byte. the rest is
+//! made of unsigned LEB128 varints, with words zig-zag encoded first so that small negative
+//! numbers stay small:
+//!
+//! ```text
+//! entry point, relative base
+//! symbol count, then for each: name length, name as utf-8 bytes, address
+//! input buffer length, words
+//! output buffer length, words
+//! segment count, then for each: start address, length in words, words
+//! ```
+//!
+//! when the `RLE` flag is set, a zero word in a segment is followed by the number of zeros in its
+//! run, so long runs of zeros only take two bytes
+
+use super::{Word, Computer};
+use super::memory::Memory;
+use super::snapshot::Snapshot;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const MAGIC: &[u8; 4] = b"\x7fICB";
+const VERSION: u8 = 1;
+
+/// flag for runs of zeros being run-length encoded
+const RLE: u8 = 1;
+
+/// the most words the segments of an image can hold, because runs of zeros let a small file
+/// describe any amount of memory. also the most words `to_code` returns
+pub const MAX_WORDS: usize = 1 << 24;
+
+#[derive(Debug)]
+pub enum IcbError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u8),
+
+    /// the data ended in the middle of a field
+    Truncated,
+
+    /// the field at byte `offset` doesn't make sense
+    Invalid { offset: usize, msg: String },
+
+    /// the image spans this many words, which is too many to hold as a program
+    TooLarge(usize),
+}
+
+impl fmt::Display for IcbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IcbError::Io(err) => write!(f, "{}", err),
+            IcbError::BadMagic => write!(f, "not an icb file"),
+            IcbError::UnsupportedVersion(version) => write!(f, "unsupported icb version {}", version),
+            IcbError::Truncated => write!(f, "icb file is truncated"),
+            IcbError::Invalid { offset, msg } => write!(f, "byte {}: {}", offset, msg),
+            IcbError::TooLarge(len) => {
+                write!(f, "image spans {} words, more than the limit of {}", len, MAX_WORDS)
+            }
+        }
+    }
+}
+
+impl Error for IcbError {
+}
+
+impl From<io::Error> for IcbError {
+    fn from(err: io::Error) -> Self {
+        IcbError::Io(err)
+    }
+}
+
+/// words stored at consecutive addresses
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Segment {
+    pub start: usize,
+    pub words: Vec<Word>,
+}
+
+/// a program or memory image, with the state needed to start running it
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Image {
+    pub segments: Vec<Segment>,
+    pub entry: usize,
+    pub rel_base: Word,
+
+    /// the address of each label
+    pub symbols: BTreeMap<String, usize>,
+
+    pub in_buf: Vec<Word>,
+    pub out_buf: Vec<Word>,
+}
+
+fn zigzag(word: Word) -> u64 {
+    ((word << 1) ^ (word >> 63)) as u64
+}
+
+fn unzigzag(val: u64) -> Word {
+    (val >> 1) as Word ^ -((val & 1) as Word)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut val: u64) {
+    while val >= 0x80 {
+        bytes.push(val as u8 | 0x80);
+        val >>= 7;
+    }
+    bytes.push(val as u8);
+}
+
+fn write_words(bytes: &mut Vec<u8>, words: &[Word]) {
+    write_varint(bytes, words.len() as u64);
+    for word in words {
+        write_varint(bytes, zigzag(*word));
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn invalid<T>(&self, msg: impl Into<String>) -> Result<T, IcbError> {
+        Err(IcbError::Invalid { offset: self.pos, msg: msg.into() })
+    }
+
+    fn byte(&mut self) -> Result<u8, IcbError> {
+        let byte = *self.bytes.get(self.pos).ok_or(IcbError::Truncated)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64, IcbError> {
+        let start = self.pos;
+        let mut val = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            val |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(val);
+            }
+        }
+
+        self.pos = start;
+        self.invalid("varint is too long")
+    }
+
+    fn word(&mut self) -> Result<Word, IcbError> {
+        self.varint().map(unzigzag)
+    }
+
+    /// a length or address, which has to fit in memory
+    fn size(&mut self) -> Result<usize, IcbError> {
+        let start = self.pos;
+        let val = self.varint()?;
+        if val > Word::MAX as u64 {
+            self.pos = start;
+            return self.invalid(format!("{} is too large", val));
+        }
+        Ok(val as usize)
+    }
+
+    /// a length of something which takes at least a byte for each item, so that a corrupt
+    /// length can't make us allocate more than the data could hold
+    fn len(&mut self) -> Result<usize, IcbError> {
+        let len = self.size()?;
+        if len > self.bytes.len() - self.pos {
+            return Err(IcbError::Truncated);
+        }
+        Ok(len)
+    }
+
+    fn words(&mut self) -> Result<Vec<Word>, IcbError> {
+        let len = self.len()?;
+        (0..len).map(|_| self.word()).collect()
+    }
+}
+
+impl Image {
+    /// a program which starts at address 0
+    pub fn from_code(code: &[Word]) -> Self {
+        Self {
+            segments: vec![Segment { start: 0, words: code.to_vec() }],
+            entry: 0,
+            rel_base: 0,
+            symbols: BTreeMap::new(),
+            in_buf: Vec::new(),
+            out_buf: Vec::new(),
+        }
+    }
+
+    /// the memory from address 0 to the end of the last segment, unless that's more than
+    /// `MAX_WORDS`. sparse images with segments at large addresses should be loaded with
+    /// `to_memory` instead
+    pub fn to_code(&self) -> Result<Vec<Word>, IcbError> {
+        let len = self.segments.iter()
+            .map(|segment| segment.start.saturating_add(segment.words.len()))
+            .max()
+            .unwrap_or(0);
+        if len > MAX_WORDS {
+            return Err(IcbError::TooLarge(len));
+        }
+
+        let mut code = vec![0; len];
+        for segment in &self.segments {
+            code[segment.start..segment.start + segment.words.len()].copy_from_slice(&segment.words);
+        }
+        Ok(code)
+    }
+
+    pub fn to_memory(&self) -> Memory {
+        let mut mem = Memory::new();
+        for segment in &self.segments {
+            for (addr, word) in (segment.start..).zip(&segment.words) {
+                mem.store(addr, *word);
+            }
+        }
+        mem
+    }
+
+    /// an image of a computer's state. each run of consecutive allocated pages becomes a segment,
+    /// without its trailing zeros
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        let mut segments: Vec<Segment> = Vec::new();
+        for (start, page) in snapshot.mem.pages() {
+            match segments.last_mut() {
+                Some(last) if last.start + last.words.len() == start => last.words.extend(page),
+                _ => segments.push(Segment { start, words: page.to_vec() }),
+            }
+        }
+
+        for segment in &mut segments {
+            let len = segment.words.iter().rposition(|word| *word != 0).map_or(0, |last| last + 1);
+            segment.words.truncate(len);
+        }
+        segments.retain(|segment| !segment.words.is_empty());
+
+        Self {
+            segments,
+            entry: snapshot.pc,
+            rel_base: snapshot.rel_offset,
+            symbols: BTreeMap::new(),
+            in_buf: snapshot.in_buf.iter().cloned().collect(),
+            out_buf: snapshot.out_buf.clone(),
+        }
+    }
+
+    pub fn to_snapshot(&self) -> Snapshot {
+        Snapshot {
+            mem: self.to_memory(),
+            pc: self.entry,
+            rel_offset: self.rel_base,
+            in_buf: self.in_buf.iter().cloned().collect(),
+            out_buf: self.out_buf.clone(),
+        }
+    }
+
+    /// a computer ready to run the image from its entry point
+    pub fn to_computer(&self) -> Computer {
+        Computer::from_snapshot(&self.to_snapshot())
+    }
+
+    /// encode the image, with runs of zeros compressed if `rle` is set
+    pub fn to_bytes(&self, rle: bool) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(if rle { RLE } else { 0 });
+
+        write_varint(&mut bytes, self.entry as u64);
+        write_varint(&mut bytes, zigzag(self.rel_base));
+
+        write_varint(&mut bytes, self.symbols.len() as u64);
+        for (name, addr) in &self.symbols {
+            write_varint(&mut bytes, name.len() as u64);
+            bytes.extend(name.as_bytes());
+            write_varint(&mut bytes, *addr as u64);
+        }
+
+        write_words(&mut bytes, &self.in_buf);
+        write_words(&mut bytes, &self.out_buf);
+
+        write_varint(&mut bytes, self.segments.len() as u64);
+        for segment in &self.segments {
+            write_varint(&mut bytes, segment.start as u64);
+            write_varint(&mut bytes, segment.words.len() as u64);
+
+            let mut words = segment.words.iter().peekable();
+            while let Some(word) = words.next() {
+                write_varint(&mut bytes, zigzag(*word));
+                if rle && *word == 0 {
+                    let mut run = 1;
+                    while words.next_if(|word| **word == 0).is_some() {
+                        run += 1;
+                    }
+                    write_varint(&mut bytes, run);
+                }
+            }
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, IcbError> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(IcbError::BadMagic);
+        }
+
+        let mut reader = Reader { bytes, pos: MAGIC.len() };
+        let version = reader.byte()?;
+        if version != VERSION {
+            return Err(IcbError::UnsupportedVersion(version));
+        }
+
+        let flags = reader.byte()?;
+        if flags & !RLE != 0 {
+            reader.pos -= 1;
+            return reader.invalid(format!("unknown flags {:#x}", flags));
+        }
+
+        let entry = reader.size()?;
+        let rel_base = reader.word()?;
+
+        let mut symbols = BTreeMap::new();
+        for _ in 0..reader.len()? {
+            let len = reader.len()?;
+            let name = match std::str::from_utf8(&bytes[reader.pos..reader.pos + len]) {
+                Ok(name) => name.to_string(),
+                Err(_) => return reader.invalid("symbol name isn't utf-8"),
+            };
+            reader.pos += len;
+            symbols.insert(name, reader.size()?);
+        }
+
+        let in_buf = reader.words()?;
+        let out_buf = reader.words()?;
+
+        let mut segments = Vec::new();
+        let mut total = 0;
+        for _ in 0..reader.len()? {
+            let start = reader.size()?;
+            let len_pos = reader.pos;
+            let len = if flags & RLE != 0 { reader.size()? } else { reader.len()? };
+
+            total += len;
+            if total > MAX_WORDS {
+                reader.pos = len_pos;
+                return reader.invalid(format!("segments hold more than {} words", MAX_WORDS));
+            }
+            if start.checked_add(len).is_none_or(|end| end > Word::MAX as usize) {
+                reader.pos = len_pos;
+                return reader.invalid("segment runs past the last address");
+            }
+
+            let mut words = Vec::with_capacity(len);
+            while words.len() < len {
+                let word = reader.word()?;
+                let run = if flags & RLE != 0 && word == 0 { reader.size()? } else { 1 };
+                if run == 0 || run > len - words.len() {
+                    return reader.invalid("zero run doesn't fit in its segment");
+                }
+                words.resize(words.len() + run, word);
+            }
+
+            segments.push(Segment { start, words });
+        }
+
+        if reader.pos != bytes.len() {
+            return reader.invalid("unexpected data after the last segment");
+        }
+
+        Ok(Self { segments, entry, rel_base, symbols, in_buf, out_buf })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, rle: bool) -> Result<(), IcbError> {
+        fs::write(path, self.to_bytes(rle))?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, IcbError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{from_str, StopReason};
+
+    #[test]
+    fn round_trips_programs() {
+        let code = from_str("1101,-1,0,20,109,-1000000,99,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0");
+        let mut image = Image::from_code(&code);
+        image.symbols.insert("start".to_string(), 0);
+
+        let plain = image.to_bytes(false);
+        let packed = image.to_bytes(true);
+        assert!(packed.len() < plain.len());
+        assert_eq!(&packed[..6], b"\x7fICB\x01\x01");
+
+        for bytes in [plain, packed].iter() {
+            let decoded = Image::from_bytes(bytes).unwrap();
+            assert_eq!(decoded, image);
+            assert_eq!(decoded.to_code().unwrap(), code);
+        }
+    }
+
+    #[test]
+    fn round_trips_snapshots() {
+        let mut computer = Computer::new(from_str("3,20,3,21,1,20,21,22,4,22,99"));
+        computer.in_buf.push_back(5);
+        assert_eq!(computer.run_until(None), Ok(StopReason::NeedsInput));
+        computer.mem_store(5_000_000, -3);
+
+        let snapshot = computer.snapshot();
+        let image = Image::from_bytes(&Image::from_snapshot(&snapshot).to_bytes(true)).unwrap();
+        assert_eq!(image.segments.len(), 2);
+        assert_eq!(image.to_snapshot().to_text(), snapshot.to_text());
+
+        let mut restored = image.to_computer();
+        restored.in_buf.push_back(10);
+        restored.run().unwrap();
+        assert_eq!(restored.out_buf, [15]);
+    }
+
+    #[test]
+    fn rejects_bad_data() {
+        let bytes = Image::from_code(&[1, 2, 3]).to_bytes(false);
+
+        assert!(matches!(Image::from_bytes(b"1,2,3"), Err(IcbError::BadMagic)));
+        assert!(matches!(Image::from_bytes(&bytes[..bytes.len() - 1]), Err(IcbError::Truncated)));
+
+        let mut future = bytes.clone();
+        future[4] = 2;
+        assert!(matches!(Image::from_bytes(&future), Err(IcbError::UnsupportedVersion(2))));
+
+        let mut extra = bytes;
+        extra.push(0);
+        assert!(matches!(Image::from_bytes(&extra), Err(IcbError::Invalid { .. })));
+    }
+
+    #[test]
+    fn limits_decoded_size() {
+        // one segment claiming 2^62 words, made of a run of that many zeros
+        let mut huge = b"\x7fICB\x01\x01\x00\x00\x00\x00\x00\x01\x00".to_vec();
+        write_varint(&mut huge, 1 << 62);
+        huge.push(0);
+        write_varint(&mut huge, 1 << 62);
+        assert!(matches!(Image::from_bytes(&huge), Err(IcbError::Invalid { offset: 13, .. })));
+
+        let far = 1 << 40;
+        let image = Image {
+            segments: vec![Segment { start: 0, words: vec![99] }, Segment { start: far, words: vec![7] }],
+            ..Image::from_code(&[])
+        };
+        let image = Image::from_bytes(&image.to_bytes(true)).unwrap();
+        assert!(matches!(image.to_code(), Err(IcbError::TooLarge(len)) if len == far + 1));
+        assert_eq!(image.to_computer().mem_load(far), 7);
+    }
+}
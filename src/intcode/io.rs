@@ -0,0 +1,143 @@
+use super::{Word, Computer, StopReason};
+use super::word::IntcodeWord;
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, Sender};
+
+/// a source of input values for a running `Computer`
+pub trait IntcodeInput<W = Word> {
+    /// take the next input value, or `None` if there isn't one available yet, in which case
+    /// the computer stops with `ExecError::InputBlocked`
+    fn read(&mut self) -> Option<W>;
+}
+
+/// a destination for values output by a running `Computer`
+pub trait IntcodeOutput<W = Word> {
+    fn write(&mut self, val: W);
+}
+
+impl<W> IntcodeInput<W> for VecDeque<W> {
+    fn read(&mut self) -> Option<W> {
+        self.pop_front()
+    }
+}
+
+impl<W> IntcodeOutput<W> for VecDeque<W> {
+    fn write(&mut self, val: W) {
+        self.push_back(val);
+    }
+}
+
+impl<W> IntcodeOutput<W> for Vec<W> {
+    fn write(&mut self, val: W) {
+        self.push(val);
+    }
+}
+
+impl<W, F: FnMut() -> Option<W>> IntcodeInput<W> for F {
+    fn read(&mut self) -> Option<W> {
+        self()
+    }
+}
+
+impl<W, F: FnMut(W)> IntcodeOutput<W> for F {
+    fn write(&mut self, val: W) {
+        self(val)
+    }
+}
+
+/// input from an iterator, which blocks the computer forever once the iterator is exhausted
+pub struct IterInput<I>(pub I);
+
+impl<W, I: Iterator<Item=W>> IntcodeInput<W> for IterInput<I> {
+    fn read(&mut self) -> Option<W> {
+        self.0.next()
+    }
+}
+
+/// doesn't block waiting for a value to be sent, so a computer reading from a channel will stop
+/// with `InputBlocked` whenever the channel is empty
+impl<W> IntcodeInput<W> for Receiver<W> {
+    fn read(&mut self) -> Option<W> {
+        self.try_recv().ok()
+    }
+}
+
+/// values written after the receiver has been dropped are discarded
+impl<W> IntcodeOutput<W> for Sender<W> {
+    fn write(&mut self, val: W) {
+        let _ = self.send(val);
+    }
+}
+
+/// reading from another computer runs it until it produces its next output. if it halts, blocks
+/// on its own input or fails before producing one, there's no input available
+impl<W: IntcodeWord> IntcodeInput<W> for Computer<W> {
+    fn read(&mut self) -> Option<W> {
+        loop {
+            if !self.out_buf.is_empty() {
+                break self.out_buf.pop_front();
+            }
+
+            match self.run_until(None) {
+                Ok(StopReason::OutputProduced)
+                | Ok(StopReason::BreakpointHit(_))
+                | Ok(StopReason::WatchpointHit(_))
+                | Ok(StopReason::StepLimitReached) => continue,
+
+                Ok(StopReason::Halted) | Ok(StopReason::NeedsInput) | Err(_) => break None,
+            }
+        }
+    }
+}
+
+/// writing to another computer adds the value to the end of its input buffer
+impl<W: IntcodeWord> IntcodeOutput<W> for Computer<W> {
+    fn write(&mut self, val: W) {
+        self.in_buf.push_back(val);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{from_str, ExecError};
+    use std::sync::mpsc::channel;
+
+    const DOUBLER: &str = "3,9,1002,9,2,9,4,9,1105,1,0";
+
+    #[test]
+    fn reads_from_closure_and_iterator() {
+        let mut computer = Computer::new(from_str(DOUBLER));
+        let mut next = 0;
+        let mut input = || {
+            next += 1;
+            if next <= 3 { Some(next) } else { None }
+        };
+        let mut out = Vec::new();
+
+        assert_eq!(computer.run_with(&mut input, &mut out), Err(ExecError::InputBlocked));
+        assert_eq!(out, [2, 4, 6]);
+
+        let mut sum = 0;
+        let result = computer.run_with(&mut IterInput(10..12), &mut |val| sum += val);
+        assert_eq!(result, Err(ExecError::InputBlocked));
+        assert_eq!(sum, 20 + 22);
+    }
+
+    #[test]
+    fn pipes_between_channels_and_computers() {
+        let (in_tx, mut in_rx) = channel();
+        let (mut out_tx, out_rx) = channel();
+        in_tx.send(5).unwrap();
+
+        let mut first = Computer::new(from_str(DOUBLER));
+        let mut second = Computer::new(from_str(DOUBLER));
+        let mut third = Computer::new(from_str(DOUBLER));
+
+        // first writes into second's input buffer, third pulls its input from second
+        let _ = first.run_with(&mut in_rx, &mut second);
+        let _ = third.run_with(&mut second, &mut out_tx);
+
+        assert_eq!(out_rx.try_recv(), Ok(40));
+    }
+}
@@ -0,0 +1,1225 @@
+//! a compiler for a small language, for writing programs without assembling them by hand:
+//!
+//! ```text
+//! // globals start as zero unless they're given a constant value, and arrays have a fixed size
+//! var count = 3;
+//! var squares[10];
+//!
+//! fn square(n) {
+//!     return n * n;
+//! }
+//!
+//! fn main() {
+//!     var i = 0;
+//!     while i < count {
+//!         squares[i] = square(input());
+//!         output(squares[i]);
+//!         i = i + 1;
+//!     }
+//! }
+//! ```
+//!
+//! every value is a word. the operators are `+`, `-` and `*`, comparisons, which give 1 or 0, and
+//! `!`, `&&` and `||`, which short-circuit. there's no division, because intcode has no
+//! instruction for it. `if`, `else`, `while`, `break` and `continue` work like in C, with braces
+//! required around blocks. a program runs by calling `main`, and halts when it returns.
+//!
+//! programs are compiled to assembler source. each call gets a stack frame addressed by the
+//! relative base, holding the return address at `rb+0`, then the parameters, locals and
+//! temporary values. return values are passed in a global. arrays have to be global, because
+//! indexing one works by rewriting the address in the instruction which accesses it, and the
+//! address of the stack isn't known. indexes aren't checked
+
+use super::Word;
+use super::asm::{self, assemble_with_symbols};
+use super::symbols::{SymbolKind, SymbolTable};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// an error in source code, at a 1-based line and column
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct CompileError {
+    pub line: usize,
+    pub col: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.col, self.msg)
+    }
+}
+
+impl Error for CompileError {
+}
+
+pub type CompileResult<T> = Result<T, CompileError>;
+
+/// a line and column
+type Pos = (usize, usize);
+
+fn error<T>(pos: Pos, msg: impl Into<String>) -> CompileResult<T> {
+    Err(CompileError { line: pos.0, col: pos.1, msg: msg.into() })
+}
+
+/// longer punctuation first, so that `<=` isn't read as `<`
+const PUNCTUATION: &[&str] = &[
+    "&&", "||", "==", "!=", "<=", ">=",
+    "+", "-", "*", "<", ">", "=", "!", "(", ")", "{", "}", "[", "]", ",", ";",
+];
+
+const KEYWORDS: &[&str] = &["fn", "var", "if", "else", "while", "return", "break", "continue"];
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+enum Tok {
+    Num(Word),
+    Ident(String),
+    Punct(&'static str),
+    End,
+}
+
+impl fmt::Display for Tok {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tok::Num(num) => write!(f, "{}", num),
+            Tok::Ident(name) => write!(f, "{}", name),
+            Tok::Punct(punct) => write!(f, "{}", punct),
+            Tok::End => write!(f, "end of file"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> CompileResult<Vec<(Tok, Pos)>> {
+    let mut tokens = Vec::new();
+
+    for (line_index, line) in source.lines().enumerate() {
+        let line = line.split("//").next().unwrap_or("");
+        let mut rest = line;
+
+        loop {
+            rest = rest.trim_start();
+            let pos = (line_index + 1, line.len() - rest.len() + 1);
+            let c = match rest.chars().next() {
+                Some(c) => c,
+                None => break,
+            };
+
+            let len = if c.is_ascii_digit() {
+                let len = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+                match rest[..len].parse() {
+                    Ok(num) => tokens.push((Tok::Num(num), pos)),
+                    Err(_) => return error(pos, format!("bad number: {}", &rest[..len])),
+                }
+                len
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let len = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+                tokens.push((Tok::Ident(rest[..len].to_string()), pos));
+                len
+            } else {
+                match PUNCTUATION.iter().find(|punct| rest.starts_with(*punct)) {
+                    Some(punct) => {
+                        tokens.push((Tok::Punct(punct), pos));
+                        punct.len()
+                    }
+                    None => return error(pos, format!("unexpected character '{}'", c)),
+                }
+            };
+
+            rest = &rest[len..];
+        }
+    }
+
+    let end = match source.lines().enumerate().last() {
+        Some((index, line)) => (index + 1, line.len() + 1),
+        None => (1, 1),
+    };
+    tokens.push((Tok::End, end));
+    Ok(tokens)
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+/// binary operators from the loosest binding to the tightest
+const PRECEDENCE: &[&[(&str, BinOp)]] = &[
+    &[("||", BinOp::Or)],
+    &[("&&", BinOp::And)],
+    &[("==", BinOp::Eq), ("!=", BinOp::Ne)],
+    &[("<", BinOp::Lt), (">", BinOp::Gt), ("<=", BinOp::Le), (">=", BinOp::Ge)],
+    &[("+", BinOp::Add), ("-", BinOp::Sub)],
+    &[("*", BinOp::Mul)],
+];
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Num(Word),
+    Var(String, Pos),
+    Index(String, Box<Expr>, Pos),
+    Call(String, Vec<Expr>, Pos),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug)]
+enum StmtKind {
+    Var(String, Option<Expr>),
+    Assign(Expr, Expr),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    Return(Option<Expr>),
+    Break,
+    Continue,
+    Expr(Expr),
+}
+
+#[derive(Clone, Debug)]
+struct Stmt {
+    pos: Pos,
+    kind: StmtKind,
+}
+
+struct Function {
+    name: String,
+    params: Vec<String>,
+    body: Vec<Stmt>,
+    pos: Pos,
+}
+
+struct Global {
+    name: String,
+
+    /// the length, if it's an array
+    len: Option<usize>,
+    init: Word,
+    pos: Pos,
+}
+
+struct Parser {
+    tokens: Vec<(Tok, Pos)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Tok {
+        &self.tokens[self.pos].0
+    }
+
+    fn peek_pos(&self) -> Pos {
+        self.tokens[self.pos].1
+    }
+
+    fn next(&mut self) -> (Tok, Pos) {
+        let token = self.tokens[self.pos].clone();
+        if token.0 != Tok::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        match self.peek() {
+            Tok::Punct(p) if *p == punct => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Tok::Ident(name) if name == keyword => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn unexpected<T>(&self) -> CompileResult<T> {
+        error(self.peek_pos(), format!("unexpected {}", self.peek()))
+    }
+
+    fn expect(&mut self, punct: &str) -> CompileResult<()> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            error(self.peek_pos(), format!("expected {}, found {}", punct, self.peek()))
+        }
+    }
+
+    fn ident(&mut self) -> CompileResult<(String, Pos)> {
+        match self.next() {
+            (Tok::Ident(name), pos) if !KEYWORDS.contains(&&name[..]) => Ok((name, pos)),
+            (tok, pos) => error(pos, format!("expected a name, found {}", tok)),
+        }
+    }
+
+    fn number(&mut self) -> CompileResult<Word> {
+        let negative = self.eat("-");
+        match self.next() {
+            (Tok::Num(num), _) if negative => Ok(-num),
+            (Tok::Num(num), _) => Ok(num),
+            (tok, pos) => error(pos, format!("expected a number, found {}", tok)),
+        }
+    }
+
+    fn program(&mut self) -> CompileResult<(Vec<Global>, Vec<Function>)> {
+        let mut globals = Vec::new();
+        let mut functions = Vec::new();
+
+        loop {
+            if self.eat_keyword("var") {
+                let (name, pos) = self.ident()?;
+                let mut global = Global { name, len: None, init: 0, pos };
+
+                if self.eat("[") {
+                    let len_pos = self.peek_pos();
+                    match self.number()? {
+                        len if len <= 0 => return error(len_pos, "arrays need at least one element"),
+                        len if len > asm::MAX_LEN as Word => {
+                            let msg = format!("arrays can't have more than {} elements", asm::MAX_LEN);
+                            return error(len_pos, msg);
+                        }
+                        len => global.len = Some(len as usize),
+                    }
+                    self.expect("]")?;
+                } else if self.eat("=") {
+                    global.init = self.number()?;
+                }
+
+                self.expect(";")?;
+                globals.push(global);
+            } else if self.eat_keyword("fn") {
+                let (name, pos) = self.ident()?;
+                self.expect("(")?;
+                let mut params = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        params.push(self.ident()?.0);
+                        if !self.eat(",") {
+                            break;
+                        }
+                    }
+                    self.expect(")")?;
+                }
+
+                let body = self.block()?;
+                functions.push(Function { name, params, body, pos });
+            } else if *self.peek() == Tok::End {
+                break Ok((globals, functions));
+            } else {
+                break self.unexpected();
+            }
+        }
+    }
+
+    fn block(&mut self) -> CompileResult<Vec<Stmt>> {
+        self.expect("{")?;
+        let mut stmts = Vec::new();
+        while !self.eat("}") {
+            stmts.push(self.stmt()?);
+        }
+        Ok(stmts)
+    }
+
+    fn stmt(&mut self) -> CompileResult<Stmt> {
+        let pos = self.peek_pos();
+
+        let kind = if self.eat_keyword("var") {
+            let name = self.ident()?.0;
+            let init = if self.eat("=") { Some(self.expr()?) } else { None };
+            self.expect(";")?;
+            StmtKind::Var(name, init)
+        } else if self.eat_keyword("if") {
+            return self.if_stmt(pos);
+        } else if self.eat_keyword("while") {
+            let cond = self.expr()?;
+            StmtKind::While(cond, self.block()?)
+        } else if self.eat_keyword("return") {
+            let val = if self.eat(";") {
+                None
+            } else {
+                let val = self.expr()?;
+                self.expect(";")?;
+                Some(val)
+            };
+            StmtKind::Return(val)
+        } else if self.eat_keyword("break") {
+            self.expect(";")?;
+            StmtKind::Break
+        } else if self.eat_keyword("continue") {
+            self.expect(";")?;
+            StmtKind::Continue
+        } else {
+            let expr = self.expr()?;
+            let kind = if self.eat("=") {
+                match expr {
+                    Expr::Var(..) | Expr::Index(..) => StmtKind::Assign(expr, self.expr()?),
+                    _ => return error(pos, "can only assign to a variable or array element"),
+                }
+            } else {
+                StmtKind::Expr(expr)
+            };
+            self.expect(";")?;
+            kind
+        };
+
+        Ok(Stmt { pos, kind })
+    }
+
+    /// the rest of an `if` statement, after the keyword
+    fn if_stmt(&mut self, pos: Pos) -> CompileResult<Stmt> {
+        let cond = self.expr()?;
+        let then = self.block()?;
+
+        let otherwise = if self.eat_keyword("else") {
+            let else_pos = self.peek_pos();
+            if self.eat_keyword("if") {
+                vec![self.if_stmt(else_pos)?]
+            } else {
+                self.block()?
+            }
+        } else {
+            Vec::new()
+        };
+
+        Ok(Stmt { pos, kind: StmtKind::If(cond, then, otherwise) })
+    }
+
+    fn expr(&mut self) -> CompileResult<Expr> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> CompileResult<Expr> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+
+        let mut lhs = self.binary(level + 1)?;
+        'outer: loop {
+            for (punct, op) in PRECEDENCE[level] {
+                if self.eat(punct) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Expr::Binary(*op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            break Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> CompileResult<Expr> {
+        if self.eat("-") {
+            match self.unary()? {
+                Expr::Num(num) => Ok(Expr::Num(num.wrapping_neg())),
+                expr => Ok(Expr::Neg(Box::new(expr))),
+            }
+        } else if self.eat("!") {
+            Ok(Expr::Not(Box::new(self.unary()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> CompileResult<Expr> {
+        match self.peek().clone() {
+            Tok::Num(num) => {
+                self.next();
+                Ok(Expr::Num(num))
+            }
+
+            Tok::Punct("(") => {
+                self.next();
+                let expr = self.expr()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+
+            Tok::Ident(_) => {
+                let (name, pos) = self.ident()?;
+                if self.eat("(") {
+                    let mut args = Vec::new();
+                    if !self.eat(")") {
+                        loop {
+                            args.push(self.expr()?);
+                            if !self.eat(",") {
+                                break;
+                            }
+                        }
+                        self.expect(")")?;
+                    }
+                    Ok(Expr::Call(name, args, pos))
+                } else if self.eat("[") {
+                    let index = self.expr()?;
+                    self.expect("]")?;
+                    Ok(Expr::Index(name, Box::new(index), pos))
+                } else {
+                    Ok(Expr::Var(name, pos))
+                }
+            }
+
+            _ => self.unexpected(),
+        }
+    }
+}
+
+/// where a value is, as an operand for an instruction
+#[derive(Clone, Debug)]
+enum Val {
+    Imm(Word),
+
+    /// a word in the stack frame
+    Slot(usize),
+
+    /// a global and an offset from it
+    Global(String, Word),
+}
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Val::Imm(num) => write!(f, "#{}", num),
+            Val::Slot(slot) => write!(f, "rb+{}", slot),
+            Val::Global(label, 0) => write!(f, "[{}]", label),
+            Val::Global(label, offset) => write!(f, "[{}{:+}]", label, offset),
+        }
+    }
+}
+
+// user names are given prefixes with a dot, which they can't contain, so that they can't clash
+// with the compiler's own labels or anything the assembler treats specially
+fn fn_label(name: &str) -> String {
+    format!("fn.{}", name)
+}
+
+fn var_label(name: &str) -> String {
+    format!("var.{}", name)
+}
+
+const RET: &str = ".ret";
+
+const BUILTINS: &[&str] = &["input", "output"];
+
+#[derive(Default)]
+struct Codegen {
+    asm: String,
+
+    /// the source line each line of `asm` came from, or 0 for lines that aren't from a statement
+    asm_lines: Vec<usize>,
+    line: usize,
+    labels: usize,
+
+    /// globals, with their lengths if they're arrays
+    globals: HashMap<String, Option<usize>>,
+
+    /// functions and the number of parameters they take
+    functions: HashMap<String, usize>,
+
+    /// the stack frame slots of the variables in each block of the current function
+    scopes: Vec<HashMap<String, usize>>,
+
+    /// the next stack frame slot which isn't in use
+    top: usize,
+
+    /// the labels `continue` and `break` jump to in each loop being compiled
+    loops: Vec<(String, String)>,
+}
+
+impl Codegen {
+    fn emit(&mut self, instruction: String) {
+        self.asm.push_str("        ");
+        self.asm.push_str(&instruction);
+        self.asm.push('\n');
+        self.asm_lines.push(self.line);
+    }
+
+    fn label(&mut self, label: &str) {
+        self.asm.push_str(label);
+        self.asm.push_str(":\n");
+        self.asm_lines.push(self.line);
+    }
+
+    fn new_label(&mut self) -> String {
+        self.labels += 1;
+        format!(".L{}", self.labels)
+    }
+
+    fn temp(&mut self) -> usize {
+        self.top += 1;
+        self.top - 1
+    }
+
+    fn program(&mut self, globals: &[Global], functions: &[Function]) -> CompileResult<()> {
+        let mut data_len = 0;
+        for global in globals {
+            if self.globals.insert(global.name.clone(), global.len).is_some() {
+                return error(global.pos, format!("{} is already defined", global.name));
+            }
+
+            data_len += global.len.unwrap_or(1);
+            if data_len > asm::MAX_LEN {
+                return error(global.pos, format!("globals can't take more than {} words", asm::MAX_LEN));
+            }
+        }
+        for function in functions {
+            if BUILTINS.contains(&&function.name[..]) || self.functions.contains_key(&function.name) {
+                return error(function.pos, format!("{} is already defined", function.name));
+            }
+            self.functions.insert(function.name.clone(), function.params.len());
+        }
+
+        match functions.iter().find(|function| function.name == "main") {
+            Some(main) if !main.params.is_empty() => return error(main.pos, "main can't take parameters"),
+            Some(_) => {}
+            None => return error((1, 1), "no main function"),
+        }
+
+        // call main with a return address that halts
+        self.emit("ARB #.stack".to_string());
+        self.emit("ADD #.halt, #0, rb+0".to_string());
+        self.emit(format!("JZ #0, {}", fn_label("main")));
+        self.label(".halt");
+        self.emit("HALT".to_string());
+
+        for function in functions {
+            self.function(function)?;
+        }
+
+        self.line = 0;
+        self.label(RET);
+        self.emit("db 0".to_string());
+        for global in globals {
+            self.label(&var_label(&global.name));
+            match global.len {
+                Some(len) => self.emit(format!("ds {}", len)),
+                None => self.emit(format!("db {}", global.init)),
+            }
+        }
+        self.label(".stack");
+
+        Ok(())
+    }
+
+    fn function(&mut self, function: &Function) -> CompileResult<()> {
+        self.line = function.pos.0;
+        self.label(&fn_label(&function.name));
+
+        let mut params = HashMap::new();
+        for (i, param) in function.params.iter().enumerate() {
+            if params.insert(param.clone(), i + 1).is_some() {
+                return error(function.pos, format!("{} has two parameters named {}", function.name, param));
+            }
+        }
+        self.scopes = vec![params];
+        self.top = function.params.len() + 1;
+
+        self.block(&function.body)?;
+        self.ret(Val::Imm(0));
+        Ok(())
+    }
+
+    fn ret(&mut self, val: Val) {
+        self.emit(format!("ADD {}, #0, [{}]", val, RET));
+        self.emit("JZ #0, rb+0".to_string());
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) -> CompileResult<()> {
+        let top = self.top;
+        self.scopes.push(HashMap::new());
+
+        for stmt in stmts {
+            self.stmt(stmt)?;
+        }
+
+        self.scopes.pop();
+        self.top = top;
+        Ok(())
+    }
+
+    /// evaluate `expr` for its value, which stays valid until the next statement
+    fn value(&mut self, expr: &Expr) -> CompileResult<Val> {
+        let top = self.top;
+        let val = self.expr(expr)?;
+        self.top = top;
+        Ok(val)
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> CompileResult<()> {
+        self.line = stmt.pos.0;
+
+        match &stmt.kind {
+            StmtKind::Var(name, init) => {
+                let val = match init {
+                    Some(init) => self.value(init)?,
+                    None => Val::Imm(0),
+                };
+
+                let slot = self.temp();
+                self.emit(format!("ADD {}, #0, rb+{}", val, slot));
+                if self.scopes.last_mut().unwrap().insert(name.clone(), slot).is_some() {
+                    return error(stmt.pos, format!("{} is already defined in this block", name));
+                }
+            }
+
+            StmtKind::Assign(target, val) => {
+                let top = self.top;
+                let mut val = self.expr(val)?;
+                if let Expr::Index(_, index, _) = target {
+                    if has_calls(index) {
+                        val = self.settle(val);
+                    }
+                }
+                match target {
+                    Expr::Var(name, pos) => {
+                        let var = self.var(name, *pos)?;
+                        self.emit(format!("ADD {}, #0, {}", val, var));
+                    }
+                    Expr::Index(name, index, pos) => {
+                        let label = self.array(name, *pos)?;
+                        match self.expr(index)? {
+                            Val::Imm(index) => {
+                                self.emit(format!("ADD {}, #0, {}", val, Val::Global(label, index)));
+                            }
+                            index => {
+                                let store = self.new_label();
+                                self.emit(format!("ADD #{}, {}, [{}+3]", label, index, store));
+                                self.label(&store);
+                                self.emit(format!("ADD {}, #0, [0]", val));
+                            }
+                        }
+                    }
+                    _ => unreachable!("the parser only allows assignments to variables"),
+                }
+                self.top = top;
+            }
+
+            StmtKind::If(cond, then, otherwise) => {
+                let cond = self.value(cond)?;
+                let else_label = self.new_label();
+                self.emit(format!("JZ {}, {}", cond, else_label));
+                self.block(then)?;
+
+                if otherwise.is_empty() {
+                    self.label(&else_label);
+                } else {
+                    let end_label = self.new_label();
+                    self.emit(format!("JZ #0, {}", end_label));
+                    self.label(&else_label);
+                    self.block(otherwise)?;
+                    self.label(&end_label);
+                }
+            }
+
+            StmtKind::While(cond, body) => {
+                let (top_label, end_label) = (self.new_label(), self.new_label());
+                self.label(&top_label);
+                let cond = self.value(cond)?;
+                self.emit(format!("JZ {}, {}", cond, end_label));
+
+                self.loops.push((top_label.clone(), end_label.clone()));
+                self.block(body)?;
+                self.loops.pop();
+
+                self.line = stmt.pos.0;
+                self.emit(format!("JZ #0, {}", top_label));
+                self.label(&end_label);
+            }
+
+            StmtKind::Return(val) => {
+                let val = match val {
+                    Some(val) => self.value(val)?,
+                    None => Val::Imm(0),
+                };
+                self.ret(val);
+            }
+
+            StmtKind::Break | StmtKind::Continue => {
+                let (continue_label, break_label) = match self.loops.last() {
+                    Some(labels) => labels.clone(),
+                    None => return error(stmt.pos, "not in a loop"),
+                };
+                let label = if let StmtKind::Break = stmt.kind { break_label } else { continue_label };
+                self.emit(format!("JZ #0, {}", label));
+            }
+
+            StmtKind::Expr(expr) => {
+                self.value(expr)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// the operand for a variable that isn't an array
+    fn var(&self, name: &str, pos: Pos) -> CompileResult<Val> {
+        if let Some(slot) = self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            return Ok(Val::Slot(*slot));
+        }
+
+        match self.globals.get(name) {
+            Some(None) => Ok(Val::Global(var_label(name), 0)),
+            Some(Some(_)) => error(pos, format!("{} is an array", name)),
+            None => error(pos, format!("{} isn't defined", name)),
+        }
+    }
+
+    /// the label of an array
+    fn array(&self, name: &str, pos: Pos) -> CompileResult<String> {
+        if self.scopes.iter().any(|scope| scope.contains_key(name)) {
+            return error(pos, format!("{} isn't an array", name));
+        }
+
+        match self.globals.get(name) {
+            Some(Some(_)) => Ok(var_label(name)),
+            Some(None) => error(pos, format!("{} isn't an array", name)),
+            None => error(pos, format!("{} isn't defined", name)),
+        }
+    }
+
+    /// copy a global into a temporary slot, so that it keeps its current value when code that
+    /// could change the global runs before it's used
+    fn settle(&mut self, val: Val) -> Val {
+        match val {
+            Val::Global(..) => {
+                let dest = self.temp();
+                self.emit(format!("ADD {}, #0, rb+{}", val, dest));
+                Val::Slot(dest)
+            }
+            val => val,
+        }
+    }
+
+    /// emit code to evaluate `expr`, returning where its value is. temporary values are put in
+    /// stack frame slots from `top` upwards, and left in use
+    fn expr(&mut self, expr: &Expr) -> CompileResult<Val> {
+        let top = self.top;
+
+        let val = match expr {
+            Expr::Num(num) => Val::Imm(*num),
+            Expr::Var(name, pos) => self.var(name, *pos)?,
+
+            Expr::Index(name, index, pos) => {
+                let label = self.array(name, *pos)?;
+                match self.expr(index)? {
+                    Val::Imm(index) => Val::Global(label, index),
+                    index => {
+                        let load = self.new_label();
+                        self.emit(format!("ADD #{}, {}, [{}+1]", label, index, load));
+                        self.top = top;
+                        let dest = self.temp();
+                        self.label(&load);
+                        self.emit(format!("ADD [0], #0, rb+{}", dest));
+                        Val::Slot(dest)
+                    }
+                }
+            }
+
+            Expr::Call(name, args, pos) => self.call(name, args, *pos)?,
+
+            Expr::Neg(expr) => {
+                let val = self.expr(expr)?;
+                self.top = top;
+                let dest = self.temp();
+                self.emit(format!("MUL {}, #-1, rb+{}", val, dest));
+                Val::Slot(dest)
+            }
+
+            Expr::Not(expr) => {
+                let val = self.expr(expr)?;
+                self.top = top;
+                let dest = self.temp();
+                self.emit(format!("EQ {}, #0, rb+{}", val, dest));
+                Val::Slot(dest)
+            }
+
+            Expr::Binary(op @ BinOp::And, lhs, rhs) | Expr::Binary(op @ BinOp::Or, lhs, rhs) => {
+                // dest holds the inverse of the result until the end, so that it can be tested
+                // to skip the right hand side
+                let dest = self.temp();
+                let skip = self.new_label();
+
+                let lhs = self.expr(lhs)?;
+                self.emit(format!("EQ {}, #0, rb+{}", lhs, dest));
+                let jump = if *op == BinOp::And { "JNZ" } else { "JZ" };
+                self.emit(format!("{} rb+{}, {}", jump, dest, skip));
+
+                self.top = dest + 1;
+                let rhs = self.expr(rhs)?;
+                self.emit(format!("EQ {}, #0, rb+{}", rhs, dest));
+                self.label(&skip);
+                self.emit(format!("EQ rb+{}, #0, rb+{}", dest, dest));
+
+                self.top = dest + 1;
+                Val::Slot(dest)
+            }
+
+            Expr::Binary(op, lhs, rhs) => {
+                let mut lhs = self.expr(lhs)?;
+                if has_calls(rhs) {
+                    lhs = self.settle(lhs);
+                }
+                let rhs = self.expr(rhs)?;
+                self.top = top;
+                let dest = self.temp();
+
+                let (mnemonic, a, b, negate) = match op {
+                    BinOp::Add => ("ADD", lhs, rhs, false),
+                    BinOp::Mul => ("MUL", lhs, rhs, false),
+                    BinOp::Sub => {
+                        // not negated into dest, which might be where lhs is
+                        let neg = self.temp();
+                        self.emit(format!("MUL {}, #-1, rb+{}", rhs, neg));
+                        self.top = dest + 1;
+                        ("ADD", lhs, Val::Slot(neg), false)
+                    }
+                    BinOp::Lt => ("LT", lhs, rhs, false),
+                    BinOp::Gt => ("LT", rhs, lhs, false),
+                    BinOp::Le => ("LT", rhs, lhs, true),
+                    BinOp::Ge => ("LT", lhs, rhs, true),
+                    BinOp::Eq => ("EQ", lhs, rhs, false),
+                    BinOp::Ne => ("EQ", lhs, rhs, true),
+                    BinOp::And | BinOp::Or => unreachable!("handled above"),
+                };
+
+                self.emit(format!("{} {}, {}, rb+{}", mnemonic, a, b, dest));
+                if negate {
+                    self.emit(format!("EQ rb+{}, #0, rb+{}", dest, dest));
+                }
+                Val::Slot(dest)
+            }
+        };
+
+        Ok(val)
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr], pos: Pos) -> CompileResult<Val> {
+        let top = self.top;
+
+        let param_count = match name {
+            "input" => 0,
+            "output" => 1,
+            _ => match self.functions.get(name) {
+                Some(count) => *count,
+                None => return error(pos, format!("{} isn't a function", name)),
+            },
+        };
+        if args.len() != param_count {
+            return error(pos, format!("{} takes {} arguments, not {}", name, param_count, args.len()));
+        }
+
+        match name {
+            "input" => {
+                let dest = self.temp();
+                self.emit(format!("IN rb+{}", dest));
+                return Ok(Val::Slot(dest));
+            }
+            "output" => {
+                let val = self.expr(&args[0])?;
+                self.emit(format!("OUT {}", val));
+                self.top = top;
+                return Ok(Val::Imm(0));
+            }
+            _ => {}
+        }
+
+        // the callee's frame starts at `top`, with its return address followed by the arguments
+        self.top += 1 + args.len();
+        for (i, arg) in args.iter().enumerate() {
+            let val = self.expr(arg)?;
+            self.emit(format!("ADD {}, #0, rb+{}", val, top + 1 + i));
+            self.top = top + 1 + args.len();
+        }
+
+        let ret = self.new_label();
+        self.emit(format!("ADD #{}, #0, rb+{}", ret, top));
+        self.emit(format!("ARB #{}", top));
+        self.emit(format!("JZ #0, {}", fn_label(name)));
+        self.label(&ret);
+        self.emit(format!("ARB #-{}", top));
+
+        self.top = top;
+        let dest = self.temp();
+        self.emit(format!("ADD [{}], #0, rb+{}", RET, dest));
+        Ok(Val::Slot(dest))
+    }
+}
+
+/// whether evaluating `expr` calls a function, which could change any global
+fn has_calls(expr: &Expr) -> bool {
+    match expr {
+        Expr::Num(_) | Expr::Var(..) => false,
+        Expr::Call(..) => true,
+        Expr::Index(_, expr, _) | Expr::Neg(expr) | Expr::Not(expr) => has_calls(expr),
+        Expr::Binary(_, lhs, rhs) => has_calls(lhs) || has_calls(rhs),
+    }
+}
+
+fn generate(source: &str) -> CompileResult<Codegen> {
+    let mut parser = Parser { tokens: tokenize(source)?, pos: 0 };
+    let (globals, functions) = parser.program()?;
+
+    let mut codegen = Codegen::default();
+    codegen.program(&globals, &functions)?;
+    Ok(codegen)
+}
+
+/// compile a program to assembler source
+pub fn compile_to_asm(source: &str) -> CompileResult<String> {
+    generate(source).map(|codegen| codegen.asm)
+}
+
+/// compile a program to words that can be passed to `Computer::new`
+pub fn compile(source: &str) -> CompileResult<Vec<Word>> {
+    compile_with_symbols(source).map(|(code, _)| code)
+}
+
+/// compile a program, along with a symbol table of its functions, globals and source lines
+pub fn compile_with_symbols(source: &str) -> CompileResult<(Vec<Word>, SymbolTable)> {
+    let codegen = generate(source)?;
+    let (code, asm_symbols) = assemble_with_symbols(&codegen.asm).map_err(|err| {
+        // reported at the statement the assembly came from, if there was one
+        let line = codegen.asm_lines.get(err.line - 1).cloned().filter(|line| *line > 0).unwrap_or(1);
+        CompileError { line, col: 1, msg: format!("couldn't assemble the compiled program: {}", err) }
+    })?;
+
+    let mut symbols = SymbolTable::new();
+    for symbol in asm_symbols.symbols() {
+        match (symbol.name.strip_prefix("fn."), symbol.name.strip_prefix("var.")) {
+            (Some(name), _) => symbols.add_label(name, symbol.addr),
+            (_, Some(name)) if symbol.kind == SymbolKind::Variable => {
+                symbols.add_variable(name, symbol.addr, symbol.len);
+            }
+            _ => {}
+        }
+    }
+
+    // map addresses back through the assembler's source lines to the original source lines
+    let mut start = 0;
+    let mut current = 0;
+    for addr in 0..=code.len() {
+        let line = asm_symbols.source_line(addr)
+            .map(|asm_line| codegen.asm_lines[asm_line - 1])
+            .unwrap_or(0);
+
+        if line != current {
+            if current != 0 {
+                symbols.add_line(current, start..addr);
+            }
+            start = addr;
+            current = line;
+        }
+    }
+
+    Ok((code, symbols))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::Computer;
+
+    fn run(source: &str, input: &[Word]) -> Vec<Word> {
+        let code = compile(source).unwrap_or_else(|err| panic!("{}", err));
+        let mut computer = Computer::new(code);
+        computer.in_buf.extend(input);
+        computer.set_step_budget(Some(1_000_000));
+        computer.run().unwrap();
+        computer.out_buf.into()
+    }
+
+    #[test]
+    fn evaluates_expressions() {
+        let out = run("
+            fn main() {
+                var a = input();
+                var b = input();
+                output(a + b * 2 - -3);
+                output((a - b) * (a + b));
+                output(a < b);
+                output(a > b);
+                output(a <= 7);
+                output(a >= 8);
+                output(a == 7 && b != 7);
+                output(!a || b == 0);
+                output(-a);
+            }
+        ", &[7, 4]);
+
+        assert_eq!(out, [18, 33, 0, 1, 1, 0, 1, 0, -7]);
+    }
+
+    #[test]
+    fn runs_loops_and_branches() {
+        // collatz steps, skipping over the odd numbers in a second count
+        let out = run("
+            fn main() {
+                var n = input();
+                var steps = 0;
+                var evens = 0;
+                while 1 {
+                    if n == 1 {
+                        break;
+                    }
+                    steps = steps + 1;
+                    var half = 0;
+                    while half * 2 < n {
+                        half = half + 1;
+                    }
+                    if half * 2 == n {
+                        n = half;
+                        evens = evens + 1;
+                        continue;
+                    } else if n > 0 {
+                        n = 3 * n + 1;
+                    } else {
+                        return;
+                    }
+                }
+                output(steps);
+                output(evens);
+            }
+        ", &[6]);
+
+        assert_eq!(out, [8, 6]);
+    }
+
+    #[test]
+    fn calls_recursive_functions() {
+        let out = run("
+            var calls;
+
+            fn fib(n) {
+                calls = calls + 1;
+                if n < 2 {
+                    return n;
+                }
+                return fib(n - 1) + fib(n - 2);
+            }
+
+            fn add3(a, b, c) {
+                return a + b + c;
+            }
+
+            fn main() {
+                output(fib(input()));
+                output(calls);
+                output(add3(1, fib(5), add3(2, 3, 4)));
+            }
+        ", &[10]);
+
+        assert_eq!(out, [55, 177, 15]);
+    }
+
+    #[test]
+    fn sorts_an_array() {
+        let out = run("
+            var len = 6;
+            var items[6];
+
+            fn swap(i, j) {
+                var t = items[i];
+                items[i] = items[j];
+                items[j] = t;
+            }
+
+            fn main() {
+                var i = 0;
+                while i < len {
+                    items[i] = input();
+                    i = i + 1;
+                }
+
+                i = 1;
+                while i < len {
+                    var j = i;
+                    while j > 0 && items[j - 1] > items[j] {
+                        swap(j - 1, j);
+                        j = j - 1;
+                    }
+                    i = i + 1;
+                }
+
+                items[0] = items[0] * 10;
+                i = 0;
+                while i < len {
+                    output(items[i]);
+                    i = i + 1;
+                }
+            }
+        ", &[5, -2, 9, 0, 5, 1]);
+
+        assert_eq!(out, [-20, 0, 1, 5, 5, 9]);
+    }
+
+    #[test]
+    fn maps_symbols_to_source() {
+        let source = "var total;\n\nfn main() {\n    total = input();\n    output(total);\n}\n";
+        let (code, symbols) = compile_with_symbols(source).unwrap();
+
+        let main = symbols.symbol("main").unwrap().addr;
+        let total = symbols.symbol("total").unwrap();
+        assert_eq!((total.kind, total.len), (SymbolKind::Variable, 1));
+        assert_eq!(code[main], 203);
+        assert_eq!(symbols.source_line(main), Some(4));
+        assert_eq!(symbols.source_line(main + 2), Some(4));
+        assert_eq!(symbols.source_line(main + 6), Some(5));
+    }
+
+    #[test]
+    fn reports_errors() {
+        let err = |source| compile(source).unwrap_err().to_string();
+
+        assert_eq!(err("fn f() {}"), "1:1: no main function");
+        assert_eq!(err("fn main() {\n  x = 1;\n}"), "2:3: x isn't defined");
+        assert_eq!(err("fn main() { output(1, 2); }"), "1:13: output takes 1 arguments, not 2");
+        assert_eq!(err("fn main() { break; }"), "1:13: not in a loop");
+        assert_eq!(err("var a[2];\nfn main() { a = 1; }"), "2:13: a is an array");
+        assert_eq!(err("fn main() { var x = 1 $ 2; }"), "1:23: unexpected character '$'");
+        assert_eq!(err("fn main() { 1 = 2; }"), "1:13: can only assign to a variable or array element");
+        assert_eq!(err("fn main() { if 1 { }"), "1:21: unexpected end of file");
+        assert_eq!(err("var a[16777217];"), "1:7: arrays can't have more than 16777216 elements");
+        assert_eq!(
+            err("var a[10000000];\nvar b[10000000];\nfn main() {}"),
+            "2:5: globals can't take more than 16777216 words"
+        );
+    }
+
+    #[test]
+    fn reads_globals_before_calls_change_them() {
+        let out = run("
+            var g = 1;
+            var items[2];
+
+            fn bump() {
+                g = g + 10;
+                items[0] = items[0] + 1;
+                return 0;
+            }
+
+            fn main() {
+                output(g + bump());
+                output(g - bump());
+                output(items[0] + bump());
+                items[bump() + 1] = g;
+                output(items[1]);
+            }
+        ", &[]);
+
+        assert_eq!(out, [1, 11, 2, 31]);
+    }
+}
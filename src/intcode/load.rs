@@ -0,0 +1,198 @@
+//! loading programs from text or icb files, with errors pointing at the part of the text that's wrong
+
+use super::{Word, Computer};
+use super::icb::{self, Image, IcbError};
+use super::word::IntcodeWord;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ParseMode {
+    /// words separated by single commas, with nothing else allowed
+    Strict,
+
+    /// words separated by commas or newlines, ignoring whitespace around them, blank lines and
+    /// a trailing comma
+    Tolerant,
+}
+
+/// a word that couldn't be parsed, which is empty if a number was expected but there wasn't one
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseError {
+    /// the byte offset of `token` in the text
+    pub offset: usize,
+    pub token: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.token.is_empty() {
+            write!(f, "expected a number at byte {}", self.offset)
+        } else {
+            write!(f, "bad number at byte {}: {:?}", self.offset, self.token)
+        }
+    }
+}
+
+impl Error for ParseError {
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse(ParseError),
+    Icb(IcbError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "{}", err),
+            LoadError::Parse(err) => write!(f, "{}", err),
+            LoadError::Icb(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for LoadError {
+}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> Self {
+        LoadError::Io(err)
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(err: ParseError) -> Self {
+        LoadError::Parse(err)
+    }
+}
+
+impl From<IcbError> for LoadError {
+    fn from(err: IcbError) -> Self {
+        LoadError::Icb(err)
+    }
+}
+
+/// each piece of `input` between the separators, with its byte offset
+fn tokens<'a>(input: &'a str, seps: &'a [char]) -> impl Iterator<Item=(usize, &'a str)> + 'a {
+    input.split(seps).scan(0, |offset, token| {
+        let start = *offset;
+        *offset += token.len() + 1;
+        Some((start, token))
+    })
+}
+
+pub fn parse(input: &str, mode: ParseMode) -> Result<Vec<Word>, ParseError> {
+    parse_as(input, mode)
+}
+
+/// parse a program for a computer with a different word type
+pub fn parse_as<W: IntcodeWord>(input: &str, mode: ParseMode) -> Result<Vec<W>, ParseError> {
+    let parse_token = |offset, token: &str| {
+        token.parse().map_err(|_| ParseError { offset, token: token.to_string() })
+    };
+
+    match mode {
+        ParseMode::Strict => tokens(input, &[','])
+            .map(|(offset, token)| parse_token(offset, token))
+            .collect(),
+
+        ParseMode::Tolerant => {
+            let code = tokens(input, &[',', '\n'])
+                .filter_map(|(offset, token)| {
+                    let trimmed = token.trim_start();
+                    let offset = offset + token.len() - trimmed.len();
+                    Some((offset, trimmed.trim_end())).filter(|(_, token)| !token.is_empty())
+                })
+                .map(|(offset, token)| parse_token(offset, token))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if code.is_empty() {
+                return Err(ParseError { offset: 0, token: String::new() });
+            }
+            Ok(code)
+        }
+    }
+}
+
+fn parse_bytes(bytes: Vec<u8>) -> Result<Vec<Word>, LoadError> {
+    let text = String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(parse(&text, ParseMode::Tolerant)?)
+}
+
+/// read a program from a file, which is either text, tolerating whitespace, or an icb image. an
+/// image has to fit in `icb::MAX_WORDS` words from address 0
+pub fn load_file(path: impl AsRef<Path>) -> Result<Vec<Word>, LoadError> {
+    let bytes = fs::read(path)?;
+    if bytes.starts_with(icb::MAGIC) {
+        return Ok(Image::from_bytes(&bytes)?.to_code()?);
+    }
+    parse_bytes(bytes)
+}
+
+/// read a program from a file into a new computer. an icb image is loaded a segment at a time,
+/// so it can be sparse, and starts from its saved pc, relative base and buffers
+pub fn load_computer(path: impl AsRef<Path>) -> Result<Computer, LoadError> {
+    let bytes = fs::read(path)?;
+    if bytes.starts_with(icb::MAGIC) {
+        return Ok(Image::from_bytes(&bytes)?.to_computer());
+    }
+    Ok(Computer::new(parse_bytes(bytes)?))
+}
+
+/// read a text program for a computer with a different word type
+pub fn load_file_as<W: IntcodeWord>(path: impl AsRef<Path>) -> Result<Vec<W>, LoadError> {
+    Ok(parse_as(&fs::read_to_string(path)?, ParseMode::Tolerant)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    fn err(offset: usize, token: &str) -> Result<Vec<Word>, ParseError> {
+        Err(ParseError { offset, token: token.to_string() })
+    }
+
+    #[test]
+    fn strict_rejects_anything_extra() {
+        assert_eq!(parse("1,-2,99", ParseMode::Strict), Ok(vec![1, -2, 99]));
+        assert_eq!(parse("1,2,99\n", ParseMode::Strict), err(4, "99\n"));
+        assert_eq!(parse("1, 2", ParseMode::Strict), err(2, " 2"));
+        assert_eq!(parse("1,,2", ParseMode::Strict), err(2, ""));
+        assert_eq!(parse("", ParseMode::Strict), err(0, ""));
+
+        let bad = parse("1,2x,3", ParseMode::Strict).unwrap_err();
+        assert_eq!(bad.to_string(), "bad number at byte 2: \"2x\"");
+    }
+
+    #[test]
+    fn tolerant_skips_whitespace() {
+        assert_eq!(parse(" 1, 2,\r\n\n3 ,\n99,\n", ParseMode::Tolerant), Ok(vec![1, 2, 3, 99]));
+        assert_eq!(parse("1, 2\n 3 ?,99", ParseMode::Tolerant), err(6, "3 ?"));
+        assert_eq!(parse(" \n\n", ParseMode::Tolerant), err(0, ""));
+    }
+
+    #[test]
+    fn loads_files() {
+        let path = env::temp_dir().join(format!("intcode-load-{}.txt", std::process::id()));
+        fs::write(&path, "1101,2,3,0,99\n").unwrap();
+        assert_eq!(load_file(&path).unwrap(), [1101, 2, 3, 0, 99]);
+
+        Image::from_code(&[104, -1, 99]).save(&path, true).unwrap();
+        assert_eq!(load_file(&path).unwrap(), [104, -1, 99]);
+        assert_eq!(load_computer(&path).unwrap().mem_load(1), -1);
+
+        fs::write(&path, "").unwrap();
+        let err = load_file(&path).unwrap_err();
+        assert_eq!(err.to_string(), "expected a number at byte 0");
+
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(load_file(&path), Err(LoadError::Io(_))));
+    }
+}
@@ -0,0 +1,183 @@
+use super::Word;
+use super::word::IntcodeWord;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub const PAGE_SIZE: usize = 1024;
+
+/// always `PAGE_SIZE` words long
+type Page<W> = Box<[W]>;
+
+/// identifies the contents of a memory. two memories with the same version hold the same values,
+/// but memories holding the same values can have different versions
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct MemoryVersion {
+    id: u64,
+    writes: u64,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+impl MemoryVersion {
+    fn new() -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            writes: 0,
+        }
+    }
+}
+
+/// sparse memory made of fixed-size pages which are only allocated when written to. addresses
+/// in pages that haven't been allocated read as zero
+pub struct Memory<W = Word> {
+    pages: BTreeMap<usize, Page<W>>,
+    version: MemoryVersion,
+}
+
+/// every memory gets its own id, so that stores to a copy can't make it look like the original
+impl<W: Clone> Clone for Memory<W> {
+    fn clone(&self) -> Self {
+        Self {
+            pages: self.pages.clone(),
+            version: MemoryVersion::new(),
+        }
+    }
+}
+
+impl<W: IntcodeWord> Default for Memory<W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: IntcodeWord> Memory<W> {
+    pub fn new() -> Self {
+        Self {
+            pages: BTreeMap::new(),
+            version: MemoryVersion::new(),
+        }
+    }
+
+    pub fn load(&self, addr: usize) -> W {
+        match self.pages.get(&(addr / PAGE_SIZE)) {
+            Some(page) => page[addr % PAGE_SIZE].clone(),
+            None => W::from_i64(0),
+        }
+    }
+
+    pub fn store(&mut self, addr: usize, val: W) {
+        let page = self.pages.entry(addr / PAGE_SIZE)
+            .or_insert_with(|| vec![W::from_i64(0); PAGE_SIZE].into_boxed_slice());
+
+        page[addr % PAGE_SIZE] = val;
+        self.version.writes += 1;
+    }
+
+    pub fn version(&self) -> MemoryVersion {
+        self.version
+    }
+
+    /// the address ranges of all pages that have been allocated, in ascending order
+    pub fn touched_pages(&self) -> impl Iterator<Item=Range<usize>> + '_ {
+        self.pages.keys().map(|page| {
+            let start = page * PAGE_SIZE;
+            start..start + PAGE_SIZE
+        })
+    }
+
+    /// the start address and contents of each allocated page, in ascending order
+    pub fn pages(&self) -> impl Iterator<Item=(usize, &[W])> + '_ {
+        self.pages.iter().map(|(page, words)| (page * PAGE_SIZE, &words[..]))
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+}
+
+/// memories are equal if every address holds the same value, whether or not its page is allocated
+impl<W: IntcodeWord> PartialEq for Memory<W> {
+    fn eq(&self, other: &Self) -> bool {
+        let contains = |mem: &Memory<W>, other: &Memory<W>| {
+            mem.pages().all(|(start, page)| {
+                page.iter().enumerate().all(|(i, word)| other.load(start + i) == *word)
+            })
+        };
+
+        contains(self, other) && contains(other, self)
+    }
+}
+
+impl<W: IntcodeWord> Eq for Memory<W> {
+}
+
+impl<W: IntcodeWord> From<Vec<W>> for Memory<W> {
+    fn from(words: Vec<W>) -> Self {
+        let mut mem = Memory::new();
+        for (addr, word) in words.into_iter().enumerate() {
+            mem.store(addr, word);
+        }
+        mem
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty_memory_reads_zero() {
+        let mem = Memory::<Word>::new();
+        assert_eq!(mem.load(0), 0);
+        assert_eq!(mem.load(usize::MAX), 0);
+        assert_eq!(mem.page_count(), 0);
+    }
+
+    #[test]
+    fn store_to_empty_memory() {
+        let mut mem = Memory::<Word>::new();
+        mem.store(0, 5);
+        assert_eq!(mem.load(0), 5);
+        assert_eq!(mem.load(1), 0);
+    }
+
+    #[test]
+    fn store_at_page_boundaries() {
+        let mut mem = Memory::<Word>::from(vec![1; PAGE_SIZE]);
+        assert_eq!(mem.page_count(), 1);
+
+        mem.store(PAGE_SIZE, 2);
+        mem.store(PAGE_SIZE - 1, 3);
+
+        assert_eq!(mem.load(PAGE_SIZE - 1), 3);
+        assert_eq!(mem.load(PAGE_SIZE), 2);
+        assert_eq!(mem.load(PAGE_SIZE + 1), 0);
+        assert_eq!(mem.page_count(), 2);
+    }
+
+    #[test]
+    fn version_changes_with_contents() {
+        let mut mem = Memory::<Word>::from(vec![1, 2, 3]);
+        let version = mem.version();
+        assert!(mem.clone() == mem);
+        assert_ne!(mem.clone().version(), version);
+
+        mem.store(1, 2);
+        assert_ne!(mem.version(), version);
+    }
+
+    #[test]
+    fn store_to_huge_address_is_sparse() {
+        let mut mem = Memory::<Word>::from(vec![1, 2, 3]);
+        let far = Word::MAX as usize;
+        mem.store(far, 4);
+
+        assert_eq!(mem.load(far), 4);
+        assert_eq!(mem.load(2), 3);
+
+        let far_page = far / PAGE_SIZE * PAGE_SIZE;
+        let touched: Vec<_> = mem.touched_pages().collect();
+        assert_eq!(touched, [0..PAGE_SIZE, far_page..far_page + PAGE_SIZE]);
+    }
+}
@@ -0,0 +1,282 @@
+use super::{Word, Computer, ExecError, StopReason};
+use std::error::Error;
+use std::fmt;
+
+/// a packet output by node `src`, made up of its destination address followed by its payload
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Packet {
+    pub src: usize,
+    pub dest: Word,
+    pub payload: Vec<Word>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct NodeError {
+    pub node: usize,
+    pub err: ExecError,
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "node {} failed: {}", self.node, self.err)
+    }
+}
+
+impl Error for NodeError {
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RoundSummary {
+    pub packets_sent: usize,
+
+    /// packets sent to addresses that don't belong to a node in the network
+    pub undelivered: Vec<Packet>,
+
+    /// true if no packets were sent, and every node that hasn't halted tried to read from an
+    /// empty input queue
+    pub idle: bool,
+}
+
+type Monitor = Box<dyn FnMut(&Packet)>;
+
+struct Node {
+    computer: Computer,
+    packet: Vec<Word>,
+    halted: bool,
+}
+
+/// a group of computers which send packets to each other by address. nodes are run one at a time
+/// in address order, each until it halts or tries to read from its queue when it's empty, so
+/// that the whole network runs deterministically on a single thread
+pub struct Network {
+    nodes: Vec<Node>,
+    packet_len: usize,
+    default_input: Option<Word>,
+    monitor: Option<Monitor>,
+    idle_rounds: usize,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            packet_len: 2,
+            default_input: None,
+            monitor: None,
+            idle_rounds: 0,
+        }
+    }
+
+    /// the number of words in each packet's payload, after its destination address. defaults to 2
+    pub fn with_packet_len(mut self, packet_len: usize) -> Self {
+        self.packet_len = packet_len;
+        self
+    }
+
+    /// a value given to a node when it reads from its queue while it's empty. once a node has been
+    /// given the default value, its turn ends the next time it reads from an empty queue.
+    /// if there's no default input, a node's turn ends as soon as it reads from an empty queue
+    pub fn with_default_input(mut self, default_input: Option<Word>) -> Self {
+        self.default_input = default_input;
+        self
+    }
+
+    /// call `monitor` with every packet sent, including ones that can't be delivered
+    pub fn with_monitor(mut self, monitor: impl FnMut(&Packet) + 'static) -> Self {
+        self.monitor = Some(Box::new(monitor));
+        self
+    }
+
+    /// add a node to the network, returning its address. its `in_buf` is used as its input queue
+    pub fn add_node(&mut self, computer: Computer) -> usize {
+        self.nodes.push(Node {
+            computer,
+            packet: Vec::new(),
+            halted: false,
+        });
+        self.nodes.len() - 1
+    }
+
+    pub fn node(&self, addr: usize) -> &Computer {
+        &self.nodes[addr].computer
+    }
+
+    pub fn node_mut(&mut self, addr: usize) -> &mut Computer {
+        &mut self.nodes[addr].computer
+    }
+
+    /// the number of rounds in a row which have been idle
+    pub fn idle_rounds(&self) -> usize {
+        self.idle_rounds
+    }
+
+    pub fn all_halted(&self) -> bool {
+        self.nodes.iter().all(|node| node.halted)
+    }
+
+    /// queue a packet's payload for a node. returns false if there's no node with that address
+    pub fn send(&mut self, dest: Word, payload: &[Word]) -> bool {
+        match self.node_index(dest) {
+            Some(dest) => {
+                self.nodes[dest].computer.in_buf.extend(payload);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn node_index(&self, addr: Word) -> Option<usize> {
+        if addr >= 0 && (addr as usize) < self.nodes.len() {
+            Some(addr as usize)
+        } else {
+            None
+        }
+    }
+
+    /// give each node that hasn't halted one turn, delivering packets as soon as they're sent
+    pub fn run_round(&mut self) -> Result<RoundSummary, NodeError> {
+        let mut summary = RoundSummary {
+            idle: true,
+            ..RoundSummary::default()
+        };
+
+        for addr in 0..self.nodes.len() {
+            if self.nodes[addr].halted {
+                continue;
+            }
+
+            let mut gave_default = false;
+            loop {
+                let node = &mut self.nodes[addr];
+                let stop = node.computer.run_until(None)
+                    .map_err(|err| NodeError { node: addr, err })?;
+
+                match stop {
+                    StopReason::OutputProduced => {
+                        node.packet.extend(node.computer.out_buf.drain(..));
+                        if node.packet.len() < self.packet_len + 1 {
+                            continue;
+                        }
+
+                        let packet = Packet {
+                            src: addr,
+                            dest: node.packet[0],
+                            payload: node.packet.drain(..).skip(1).collect(),
+                        };
+                        summary.packets_sent += 1;
+                        summary.idle = false;
+
+                        if let Some(monitor) = &mut self.monitor {
+                            monitor(&packet);
+                        }
+
+                        if !self.send(packet.dest, &packet.payload) {
+                            summary.undelivered.push(packet);
+                        }
+                    }
+
+                    StopReason::NeedsInput => match self.default_input {
+                        Some(default_input) if !gave_default => {
+                            node.computer.in_buf.push_back(default_input);
+                            gave_default = true;
+                        }
+                        _ => break,
+                    },
+
+                    StopReason::Halted => {
+                        node.halted = true;
+                        summary.idle = false;
+                        break;
+                    }
+
+                    StopReason::BreakpointHit(_)
+                    | StopReason::WatchpointHit(_)
+                    | StopReason::StepLimitReached => continue,
+                }
+            }
+        }
+
+        if summary.idle {
+            self.idle_rounds += 1;
+        } else {
+            self.idle_rounds = 0;
+        }
+
+        Ok(summary)
+    }
+
+    /// run rounds until the network has been idle for `idle_rounds` rounds in a row or every
+    /// node has halted. returns all the undelivered packets sent in the meantime
+    pub fn run_until_idle(&mut self, idle_rounds: usize) -> Result<Vec<Packet>, NodeError> {
+        let mut undelivered = Vec::new();
+
+        while self.idle_rounds < idle_rounds && !self.all_halted() {
+            undelivered.extend(self.run_round()?.undelivered);
+        }
+
+        Ok(undelivered)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::asm::assemble;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn routes_packets_until_idle() {
+        let sender = assemble("
+                    IN [addr]
+                    OUT #1
+                    OUT #3
+                    OUT #4
+            idle:   IN [tmp]
+                    JZ #0, idle
+            addr:   db 0
+            tmp:    db 0
+        ").unwrap();
+
+        let adder = assemble("
+                    IN [addr]
+            loop:   IN [x]
+                    EQ [x], #-1, [empty]
+                    JNZ [empty], loop
+                    IN [y]
+                    ADD [x], [y], [sum]
+                    OUT #255
+                    OUT [sum]
+                    OUT [addr]
+                    JZ #0, loop
+            addr:   db 0
+            x:      db 0
+            y:      db 0
+            sum:    db 0
+            empty:  db 0
+        ").unwrap();
+
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let monitor_seen = seen.clone();
+        let mut network = Network::new()
+            .with_default_input(Some(-1))
+            .with_monitor(move |packet| monitor_seen.borrow_mut().push(packet.clone()));
+
+        for code in [sender, adder].iter() {
+            let addr = network.add_node(Computer::new(code.clone()));
+            network.node_mut(addr).in_buf.push_back(addr as Word);
+        }
+
+        let undelivered = network.run_until_idle(2).unwrap();
+        assert_eq!(undelivered, [Packet { src: 1, dest: 255, payload: vec![7, 1] }]);
+        assert_eq!(seen.borrow().len(), 2);
+        assert_eq!(seen.borrow()[0], Packet { src: 0, dest: 1, payload: vec![3, 4] });
+        assert_eq!(network.idle_rounds(), 2);
+    }
+}
@@ -0,0 +1,321 @@
+//! counts of what a program does while it runs, to find out where it spends its time.
+//!
+//! intcode has no call instruction, so calls are recognized by the convention the AoC programs
+//! use: the return address is written to memory, then a jump with an immediate target is taken.
+//! a later jump to that return address through memory is the matching return. the call stacks
+//! found this way are used for the folded stacks report, which tools like `flamegraph.pl` and
+//! inferno can draw
+
+use super::{Word, Op, Mode, Computer};
+use super::disasm::decode_at;
+use super::memory::Memory;
+use super::symbols::SymbolTable;
+use super::trace::TraceEvent;
+use super::word::IntcodeWord;
+use std::collections::HashMap;
+
+/// calls nested deeper than this are counted as part of the deepest frame, so that a program
+/// which never returns the way it was expected to can't make the stack grow forever
+const MAX_DEPTH: usize = 256;
+
+struct Frame {
+    entry: usize,
+    return_addr: Word,
+}
+
+/// what a computer has executed while profiling
+pub struct Profile {
+    retired: u64,
+    pcs: HashMap<usize, u64>,
+    ops: HashMap<Op, u64>,
+    reads: HashMap<usize, u64>,
+    writes: HashMap<usize, u64>,
+
+    frames: Vec<Frame>,
+
+    /// values written since the last jump, which might be the return address of a call
+    written: Vec<Word>,
+
+    /// the number of instructions executed in each call stack, identified by the entry points of
+    /// its frames
+    stacks: Vec<(Vec<usize>, u64)>,
+    stack_ids: HashMap<Vec<usize>, usize>,
+    stack: usize,
+}
+
+fn sorted<K: Copy>(counts: &HashMap<K, u64>, key: impl Fn(K) -> usize) -> Vec<(K, u64)> {
+    let mut counts: Vec<_> = counts.iter().map(|(k, count)| (*k, *count)).collect();
+    counts.sort_by_key(|(k, count)| (!count, key(*k)));
+    counts
+}
+
+fn percent(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 * 100.0 / total as f64
+    }
+}
+
+impl Profile {
+    fn new() -> Self {
+        let mut profile = Self {
+            retired: 0,
+            pcs: HashMap::new(),
+            ops: HashMap::new(),
+            reads: HashMap::new(),
+            writes: HashMap::new(),
+
+            frames: Vec::new(),
+            written: Vec::new(),
+
+            stacks: Vec::new(),
+            stack_ids: HashMap::new(),
+            stack: 0,
+        };
+        profile.enter_stack();
+        profile
+    }
+
+    /// find or add the entry for the current call stack
+    fn enter_stack(&mut self) {
+        let entries: Vec<_> = self.frames.iter().map(|frame| frame.entry).collect();
+
+        let stacks = &mut self.stacks;
+        self.stack = *self.stack_ids.entry(entries.clone()).or_insert_with(|| {
+            stacks.push((entries, 0));
+            stacks.len() - 1
+        });
+    }
+
+    /// forget the call stack, because the computer's state has been replaced
+    pub(super) fn restarted(&mut self) {
+        self.frames.clear();
+        self.written.clear();
+        self.enter_stack();
+    }
+
+    /// `rel_offset` is the relative base before `event` was executed
+    pub(super) fn record<W: IntcodeWord>(&mut self, rel_offset: &W, event: &TraceEvent<W>) {
+        self.retired += 1;
+        *self.pcs.entry(event.pc).or_insert(0) += 1;
+        *self.ops.entry(event.op).or_insert(0) += 1;
+        self.stacks[self.stack].1 += 1;
+
+        let out_param = event.op.out_param();
+        for (i, operand) in event.operands.iter().enumerate() {
+            let addr = match operand.mode {
+                _ if out_param == Some(i) => continue,
+                Mode::Immediate => continue,
+                Mode::Pointer => operand.raw.to_i64(),
+                Mode::Relative => operand.raw.add(rel_offset).and_then(|addr| addr.to_i64()),
+            };
+
+            // the instruction has executed, so its addresses were valid
+            if let Some(addr) = addr {
+                *self.reads.entry(addr as usize).or_insert(0) += 1;
+            }
+        }
+
+        for write in &event.writes {
+            *self.writes.entry(write.addr).or_insert(0) += 1;
+            self.written.extend(write.new.to_i64());
+        }
+
+        if let Op::Jnz | Op::Jz = event.op {
+            let (cond, target) = (&event.operands[0], &event.operands[1]);
+            if cond.value.is_zero() != (event.op == Op::Jnz) {
+                if let Some(addr) = target.value.to_i64() {
+                    self.jumped(event.pc, target.mode, addr);
+                }
+            }
+            self.written.clear();
+        }
+    }
+
+    fn jumped(&mut self, pc: usize, mode: Mode, target: Word) {
+        let return_addr = pc as Word + 3;
+
+        if mode != Mode::Immediate {
+            if let Some(depth) = self.frames.iter().rposition(|frame| frame.return_addr == target) {
+                self.frames.truncate(depth);
+                self.enter_stack();
+            }
+        } else if self.written.contains(&return_addr) && self.frames.len() < MAX_DEPTH {
+            self.frames.push(Frame { entry: target as usize, return_addr });
+            self.enter_stack();
+        }
+    }
+
+    /// the number of instructions executed
+    pub fn retired(&self) -> u64 {
+        self.retired
+    }
+
+    /// the number of times the instruction at `pc` was executed
+    pub fn executions(&self, pc: usize) -> u64 {
+        self.pcs.get(&pc).cloned().unwrap_or(0)
+    }
+
+    /// the number of times an operand was read from `addr`
+    pub fn reads(&self, addr: usize) -> u64 {
+        self.reads.get(&addr).cloned().unwrap_or(0)
+    }
+
+    /// the number of times an instruction wrote to `addr`
+    pub fn writes(&self, addr: usize) -> u64 {
+        self.writes.get(&addr).cloned().unwrap_or(0)
+    }
+
+    /// the address of every instruction executed and how many times, most executed first
+    pub fn hot_pcs(&self) -> Vec<(usize, u64)> {
+        sorted(&self.pcs, |pc| pc)
+    }
+
+    /// how many times each kind of instruction was executed, most executed first
+    pub fn op_counts(&self) -> Vec<(Op, u64)> {
+        sorted(&self.ops, |op| op.code() as usize)
+    }
+
+    /// every address read or written and how many times, most accessed first
+    pub fn hot_addrs(&self) -> Vec<(usize, u64, u64)> {
+        let mut accesses: HashMap<usize, u64> = self.reads.clone();
+        for (addr, count) in &self.writes {
+            *accesses.entry(*addr).or_insert(0) += count;
+        }
+
+        sorted(&accesses, |addr| addr).into_iter()
+            .map(|(addr, _)| (addr, self.reads(addr), self.writes(addr)))
+            .collect()
+    }
+
+    /// a report of the `limit` most executed instructions, disassembled from `mem`, followed by
+    /// the instruction counts by opcode and the `limit` most accessed addresses
+    pub fn to_table<W: IntcodeWord>(&self, mem: &Memory<W>, limit: usize) -> String {
+        self.to_table_with(mem, limit, &SymbolTable::new())
+    }
+
+    /// like `to_table`, with addresses followed by their symbols
+    pub fn to_table_with<W: IntcodeWord>(&self, mem: &Memory<W>, limit: usize, symbols: &SymbolTable)
+        -> String
+    {
+        let mut table = format!("{} instructions retired\n\n", self.retired);
+
+        table.push_str(&format!("{:>8} {:>12} {:>7}  instruction\n", "pc", "count", "%"));
+        for (pc, count) in self.hot_pcs().into_iter().take(limit) {
+            // words too big for an `i64` can't be part of an instruction
+            let words: Vec<_> = (pc..pc + 4).map_while(|addr| mem.load(addr).to_i64()).collect();
+            let instruction = decode_at(&words, 0)
+                .map(|instruction| instruction.to_string())
+                .unwrap_or_else(|| "?".to_string());
+
+            let percent = percent(count, self.retired);
+            table.push_str(&format!("{:>8} {:>12} {:>7.2}  {}", pc, count, percent, instruction));
+            if let Some(name) = symbols.describe(pc) {
+                table.push_str(&format!(" <{}>", name));
+            }
+            table.push('\n');
+        }
+
+        table.push_str(&format!("\n{:>8} {:>12} {:>7}\n", "op", "count", "%"));
+        for (op, count) in self.op_counts() {
+            let percent = percent(count, self.retired);
+            table.push_str(&format!("{:>8} {:>12} {:>7.2}\n", op.mnemonic(), count, percent));
+        }
+
+        table.push_str(&format!("\n{:>8} {:>12} {:>12}\n", "addr", "reads", "writes"));
+        for (addr, reads, writes) in self.hot_addrs().into_iter().take(limit) {
+            table.push_str(&format!("{:>8} {:>12} {:>12}", addr, reads, writes));
+            if let Some(name) = symbols.describe(addr) {
+                table.push_str(&format!("  <{}>", name));
+            }
+            table.push('\n');
+        }
+
+        table
+    }
+
+    /// one line for each call stack, like `main;sub_578;sub_1203 1234`, with the number of
+    /// instructions executed in that stack's innermost frame
+    pub fn to_folded(&self) -> String {
+        self.to_folded_with(&SymbolTable::new())
+    }
+
+    /// like `to_folded`, with frames named after the symbol at their entry point if there is one
+    pub fn to_folded_with(&self, symbols: &SymbolTable) -> String {
+        let mut stacks: Vec<_> = self.stacks.iter()
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        stacks.sort();
+
+        let mut folded = String::new();
+        for (entries, count) in stacks {
+            folded.push_str("main");
+            for entry in entries {
+                match symbols.describe(*entry) {
+                    Some(name) => folded.push_str(&format!(";{}", name)),
+                    None => folded.push_str(&format!(";sub_{}", entry)),
+                }
+            }
+            folded.push_str(&format!(" {}\n", count));
+        }
+        folded
+    }
+}
+
+impl<W: IntcodeWord> Computer<W> {
+    /// start counting the instructions executed and the memory they access. profiling
+    /// continues across restores, so the counts can cover many runs of a program
+    pub fn start_profiling(&mut self) {
+        self.profile = Some(Profile::new());
+    }
+
+    /// stop profiling and return what was recorded
+    pub fn stop_profiling(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::intcode::{Computer, Op, asm::assemble};
+
+    #[test]
+    fn counts_instructions_and_calls() {
+        let code = assemble("
+                    ARB #100
+                    ADD #ret, #0, rb+0
+                    JZ #0, sub
+            ret:    OUT [n]
+                    HALT
+            sub:    ADD [n], #1, [n]
+                    LT [n], #3, [more]
+                    JNZ [more], sub
+                    JZ #0, rb+0
+            n:      db 0
+            more:   db 0
+        ").unwrap();
+
+        let mut computer = Computer::new(code);
+        computer.start_profiling();
+        computer.run().unwrap();
+        let profile = computer.stop_profiling().unwrap();
+
+        assert_eq!(profile.retired(), 15);
+        assert_eq!(profile.executions(12), 3);
+        assert_eq!(profile.executions(23), 1);
+        assert_eq!(profile.hot_pcs()[0], (12, 3));
+        assert_eq!(profile.op_counts()[..2], [(Op::Add, 4), (Op::Jnz, 3)]);
+
+        assert_eq!((profile.reads(26), profile.writes(26)), (7, 3));
+        assert_eq!((profile.reads(100), profile.writes(100)), (1, 1));
+        assert_eq!(profile.hot_addrs()[0], (26, 7, 3));
+
+        assert_eq!(profile.to_folded(), "main 5\nmain;sub_12 10\n");
+        assert!(profile.to_table(computer.memory(), 3).contains("      12            3   20.00  ADD [26], #1, [26]\n"));
+    }
+}
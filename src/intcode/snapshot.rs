@@ -0,0 +1,219 @@
+//! saved machine state, which can be written to and read from a text format like:
+//!
+//! ```text
+//! intcode-snapshot 1
+//! pc 12
+//! rb 0
+//! in 1,2
+//! out
+//! mem 0 1102,34,45,7,4,7,99
+//! mem 1048576 0,0,5
+//! ```
+//!
+//! each `mem` line holds the contents of a page of memory from its start address, without
+//! trailing zeros. pages which only contain zeros are left out
+
+use super::{Word, Computer};
+use super::memory::Memory;
+use super::word::IntcodeWord;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const HEADER: &str = "intcode-snapshot 1";
+
+/// the full state of a computer's memory, registers and buffers at one point in time
+#[derive(Clone)]
+pub struct Snapshot<W = Word> {
+    pub mem: Memory<W>,
+    pub pc: usize,
+    pub rel_offset: W,
+    pub in_buf: VecDeque<W>,
+    pub out_buf: Vec<W>,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Parse { line: usize, msg: String },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "{}", err),
+            SnapshotError::Parse { line, msg } => write!(f, "line {}: {}", line, msg),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> Self {
+        SnapshotError::Io(err)
+    }
+}
+
+fn write_list<'a, W: IntcodeWord>(text: &mut String, words: impl Iterator<Item=&'a W>) {
+    let words: Vec<_> = words.map(W::to_string).collect();
+    text.push_str(&words.join(","));
+}
+
+impl<W: IntcodeWord> Snapshot<W> {
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\npc {}\nrb {}\n", HEADER, self.pc, self.rel_offset);
+
+        text.push_str("in ");
+        write_list(&mut text, self.in_buf.iter());
+        text.push_str("\nout ");
+        write_list(&mut text, self.out_buf.iter());
+        text.push('\n');
+
+        for (start, page) in self.mem.pages() {
+            let len = match page.iter().rposition(|word| !word.is_zero()) {
+                Some(last) => last + 1,
+                None => continue,
+            };
+
+            text.push_str(&format!("mem {} ", start));
+            write_list(&mut text, page[..len].iter());
+            text.push('\n');
+        }
+
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, SnapshotError> {
+        let mut lines = text.lines().enumerate();
+
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            _ => return Err(SnapshotError::Parse { line: 1, msg: "not an intcode snapshot".to_string() }),
+        }
+
+        let mut snapshot = Snapshot {
+            mem: Memory::new(),
+            pc: 0,
+            rel_offset: W::from_i64(0),
+            in_buf: VecDeque::new(),
+            out_buf: Vec::new(),
+        };
+
+        for (index, line) in lines {
+            let err = |msg: String| SnapshotError::Parse { line: index + 1, msg };
+
+            fn parse<T: std::str::FromStr>(val: &str) -> Result<T, String> {
+                val.trim().parse().map_err(|_| format!("bad number: {}", val))
+            }
+
+            fn parse_list<W: IntcodeWord>(list: &str) -> Result<Vec<W>, String> {
+                list.split(',')
+                    .filter(|val| !val.trim().is_empty())
+                    .map(parse)
+                    .collect()
+            }
+
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, val) = match line.find(' ') {
+                Some(space) => (&line[..space], &line[space + 1..]),
+                None => (line, ""),
+            };
+
+            match key {
+                "pc" => snapshot.pc = parse(val).map_err(err)?,
+                "rb" => snapshot.rel_offset = parse(val).map_err(err)?,
+                "in" => snapshot.in_buf = parse_list(val).map_err(err)?.into(),
+                "out" => snapshot.out_buf = parse_list(val).map_err(err)?,
+                "mem" => {
+                    let (start, words) = match val.find(' ') {
+                        Some(space) => (&val[..space], &val[space + 1..]),
+                        None => (val, ""),
+                    };
+
+                    let start: usize = parse(start).map_err(err)?;
+                    for (offset, word) in parse_list(words).map_err(err)?.into_iter().enumerate() {
+                        let addr = start.checked_add(offset)
+                            .ok_or_else(|| err("memory runs past the last address".to_string()))?;
+                        snapshot.mem.store(addr, word);
+                    }
+                }
+                _ => return Err(err(format!("unknown field: {}", key))),
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        fs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        Self::from_text(&fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{from_str, StopReason};
+
+    #[test]
+    fn restores_forked_state() {
+        let mut computer = Computer::new(from_str("3,20,3,21,1,20,21,22,4,22,99"));
+        computer.in_buf.push_back(5);
+        assert_eq!(computer.run_until(None), Ok(StopReason::NeedsInput));
+
+        let fork = computer.snapshot();
+        for (input, expected) in [(1, 6), (10, 15)].iter() {
+            computer.restore(&fork);
+            computer.in_buf.push_back(*input);
+            computer.run().unwrap();
+            assert_eq!(computer.out_buf, [*expected]);
+        }
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let mut computer = Computer::new(from_str("3,20,3,21,1,20,21,22,4,22,99"));
+        computer.in_buf.extend(&[7, 8, 9]);
+        computer.run().unwrap();
+        computer.mem_store(5_000_000, -3);
+
+        let text = computer.snapshot().to_text();
+        let snapshot: Snapshot = Snapshot::from_text(&text).unwrap();
+
+        assert_eq!(snapshot.to_text(), text);
+        assert_eq!(snapshot.pc, 10);
+        assert_eq!(snapshot.in_buf, [9]);
+        assert_eq!(snapshot.out_buf, [15]);
+        assert_eq!(snapshot.mem.load(5_000_000), -3);
+        assert_eq!(snapshot.mem.load(22), 15);
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        match Snapshot::<Word>::from_text("intcode-snapshot 1\npc 0\nrb x\n") {
+            Err(SnapshotError::Parse { line, .. }) => assert_eq!(line, 3),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("should fail to parse"),
+        }
+
+        let past_end = format!("intcode-snapshot 1\nmem {} 1,2\n", usize::MAX);
+        match Snapshot::<Word>::from_text(&past_end) {
+            Err(SnapshotError::Parse { line, .. }) => assert_eq!(line, 2),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("should fail to parse"),
+        }
+    }
+}
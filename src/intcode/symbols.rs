@@ -0,0 +1,277 @@
+//! names and source lines for the addresses in a program, which can be saved alongside it in a
+//! text format like:
+//!
+//! ```text
+//! ; comments run to the end of the line
+//! label loop_top 12       ; code starting at address 12
+//! var counter 40 1        ; data at addresses 40 to 40
+//! line 3 12 4             ; source line 3 assembled to addresses 12 to 15
+//! ```
+//!
+//! a label covers the addresses from its own up to the next symbol, so address 15 above is shown
+//! as `loop_top+3`
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SymbolKind {
+    Label,
+    Variable,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub addr: usize,
+
+    /// the number of words in a variable. labels don't have a length
+    pub len: usize,
+}
+
+#[derive(Debug)]
+pub enum SymbolError {
+    Io(io::Error),
+    Parse { line: usize, msg: String },
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolError::Io(err) => write!(f, "{}", err),
+            SymbolError::Parse { line, msg } => write!(f, "line {}: {}", line, msg),
+        }
+    }
+}
+
+impl Error for SymbolError {
+}
+
+impl From<io::Error> for SymbolError {
+    fn from(err: io::Error) -> Self {
+        SymbolError::Io(err)
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct SymbolTable {
+    /// sorted by address
+    symbols: Vec<Symbol>,
+
+    /// the addresses each source line assembled to, sorted by address
+    lines: Vec<(Range<usize>, usize)>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty() && self.lines.is_empty()
+    }
+
+    fn insert(&mut self, symbol: Symbol) {
+        let pos = self.symbols.partition_point(|other| other.addr <= symbol.addr);
+        self.symbols.insert(pos, symbol);
+    }
+
+    pub fn add_label(&mut self, name: &str, addr: usize) {
+        self.insert(Symbol { name: name.to_string(), kind: SymbolKind::Label, addr, len: 0 });
+    }
+
+    pub fn add_variable(&mut self, name: &str, addr: usize, len: usize) {
+        self.insert(Symbol { name: name.to_string(), kind: SymbolKind::Variable, addr, len });
+    }
+
+    pub fn add_line(&mut self, line: usize, addrs: Range<usize>) {
+        let pos = self.lines.partition_point(|(other, _)| other.start <= addrs.start);
+        self.lines.insert(pos, (addrs, line));
+    }
+
+    /// every symbol, in address order
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    /// the symbols which start at an address in `addrs`
+    pub fn symbols_in(&self, addrs: Range<usize>) -> &[Symbol] {
+        let start = self.symbols.partition_point(|symbol| symbol.addr < addrs.start);
+        let end = self.symbols.partition_point(|symbol| symbol.addr < addrs.end);
+        &self.symbols[start..end]
+    }
+
+    /// the symbol covering `addr`, and how far into it `addr` is
+    pub fn lookup(&self, addr: usize) -> Option<(&Symbol, usize)> {
+        let pos = self.symbols.partition_point(|symbol| symbol.addr <= addr);
+        let symbol = self.symbols[..pos].last()?;
+        let offset = addr - symbol.addr;
+
+        match symbol.kind {
+            SymbolKind::Variable if offset >= symbol.len => None,
+            _ => Some((symbol, offset)),
+        }
+    }
+
+    /// the source line `addr` was assembled from
+    pub fn source_line(&self, addr: usize) -> Option<usize> {
+        let pos = self.lines.partition_point(|(addrs, _)| addrs.start <= addr);
+        self.lines[..pos].iter().rev()
+            .find(|(addrs, _)| addrs.contains(&addr))
+            .map(|(_, line)| *line)
+    }
+
+    /// `addr` as a symbol and offset, like `loop_top+3`
+    pub fn describe(&self, addr: usize) -> Option<String> {
+        self.lookup(addr).map(|(symbol, offset)| match offset {
+            0 => symbol.name.clone(),
+            _ => format!("{}+{}", symbol.name, offset),
+        })
+    }
+
+    /// `addr` followed by its symbol if it has one, like `15 <loop_top+3>`
+    pub fn annotate(&self, addr: usize) -> String {
+        match self.describe(addr) {
+            Some(desc) => format!("{} <{}>", addr, desc),
+            None => addr.to_string(),
+        }
+    }
+
+    /// the address of a symbol with an optional offset, like `loop_top+3`, or a plain number
+    pub fn resolve(&self, text: &str) -> Option<usize> {
+        if let Ok(addr) = text.parse() {
+            return Some(addr);
+        }
+
+        let (name, offset) = match text.find('+') {
+            Some(plus) => (&text[..plus], text[plus + 1..].parse().ok()?),
+            None => (text, 0),
+        };
+        self.symbol(name).and_then(|symbol| symbol.addr.checked_add(offset))
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for symbol in &self.symbols {
+            match symbol.kind {
+                SymbolKind::Label => text.push_str(&format!("label {} {}\n", symbol.name, symbol.addr)),
+                SymbolKind::Variable => {
+                    text.push_str(&format!("var {} {} {}\n", symbol.name, symbol.addr, symbol.len))
+                }
+            }
+        }
+        for (addrs, line) in &self.lines {
+            text.push_str(&format!("line {} {} {}\n", line, addrs.start, addrs.len()));
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, SymbolError> {
+        let mut table = Self::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let err = |msg: String| SymbolError::Parse { line: index + 1, msg };
+            let parse = |val: Option<&str>| -> Result<usize, SymbolError> {
+                let val = val.ok_or_else(|| err("missing field".to_string()))?;
+                val.parse().map_err(|_| err(format!("bad number: {}", val)))
+            };
+
+            let line = line.split(';').next().unwrap_or("");
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                None => continue,
+                Some("label") => {
+                    let name = fields.next().ok_or_else(|| err("missing name".to_string()))?;
+                    table.add_label(name, parse(fields.next())?);
+                }
+                Some("var") => {
+                    let name = fields.next().ok_or_else(|| err("missing name".to_string()))?;
+                    let addr = parse(fields.next())?;
+                    let len = parse(fields.next())?;
+                    if addr.checked_add(len).is_none() {
+                        return Err(err("variable runs past the last address".to_string()));
+                    }
+                    table.add_variable(name, addr, len);
+                }
+                Some("line") => {
+                    let line = parse(fields.next())?;
+                    let start = parse(fields.next())?;
+                    let end = start.checked_add(parse(fields.next())?)
+                        .ok_or_else(|| err("line runs past the last address".to_string()))?;
+                    table.add_line(line, start..end);
+                }
+                Some(kind) => return Err(err(format!("unknown entry: {}", kind))),
+            }
+
+            if let Some(extra) = fields.next() {
+                return Err(err(format!("unexpected field: {}", extra)));
+            }
+        }
+
+        Ok(table)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SymbolError> {
+        fs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SymbolError> {
+        Self::from_text(&fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn looks_up_addresses() {
+        let table = SymbolTable::from_text("
+            ; a loop and its counter
+            label start 0
+            label loop_top 12
+            var counter 40 2
+            line 3 12 4
+        ").unwrap();
+
+        assert_eq!(table.describe(15), Some("loop_top+3".to_string()));
+        assert_eq!(table.describe(41), Some("counter+1".to_string()));
+        assert_eq!(table.describe(42), None);
+        assert_eq!(table.annotate(0), "0 <start>");
+        assert_eq!(table.annotate(50), "50");
+        assert_eq!(table.source_line(14), Some(3));
+        assert_eq!(table.source_line(16), None);
+        assert_eq!(table.resolve("loop_top+2"), Some(14));
+        assert_eq!(table.resolve("nowhere"), None);
+
+        let names: Vec<_> = table.symbols_in(10..41).iter().map(|symbol| &symbol.name[..]).collect();
+        assert_eq!(names, ["loop_top", "counter"]);
+
+        assert_eq!(SymbolTable::from_text(&table.to_text()).unwrap(), table);
+        assert!(SymbolTable::from_text("label x").is_err());
+    }
+
+    #[test]
+    fn rejects_addresses_past_the_end() {
+        let table = SymbolTable::from_text(&format!("label end {}", usize::MAX)).unwrap();
+        assert_eq!(table.resolve("end"), Some(usize::MAX));
+        assert_eq!(table.resolve("end+1"), None);
+
+        for text in &[format!("line 1 {} 2", usize::MAX), format!("var x {} 2", usize::MAX)] {
+            match SymbolTable::from_text(text) {
+                Err(SymbolError::Parse { line: 1, .. }) => {}
+                other => panic!("expected a parse error, got {:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use super::{Word, Computer, ExecError};
+use super::io::{IntcodeInput, IntcodeOutput};
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+/// input which is taken from the computer's own `in_buf` first, then blocks on a channel until
+/// a value is sent. only blocks the computer once all senders for the channel have been dropped
+struct BlockingInput {
+    pending: VecDeque<Word>,
+    rx: Receiver<Word>,
+}
+
+impl IntcodeInput for BlockingInput {
+    fn read(&mut self) -> Option<Word> {
+        self.pending.pop_front().or_else(|| self.rx.recv().ok())
+    }
+}
+
+/// the final state of a computer run on a thread, which is returned along with the error if it
+/// failed
+pub type MachineResult = Result<Computer, (ExecError, Box<Computer>)>;
+
+/// run a computer on its own thread until it halts or fails. values already in its `in_buf` are
+/// read before anything received on `input`, and if `input` is closed while the computer is
+/// waiting on it, it stops with `ExecError::InputBlocked`
+pub fn spawn<O>(mut computer: Computer, input: Receiver<Word>, mut output: O) -> JoinHandle<MachineResult>
+    where O: IntcodeOutput + Send + 'static
+{
+    thread::spawn(move || {
+        let mut input = BlockingInput {
+            pending: mem::take(&mut computer.in_buf),
+            rx: input,
+        };
+
+        let result = computer.run_with(&mut input, &mut output);
+        computer.in_buf = input.pending;
+
+        match result {
+            Ok(()) => Ok(computer),
+            Err(err) => Err((err, Box::new(computer))),
+        }
+    })
+}
+
+/// a group of computers running on their own threads, connected one after another
+pub struct Pipeline {
+    /// receives every value output by the last computer
+    pub output: Receiver<Word>,
+
+    input: Option<Sender<Word>>,
+    /// the sender a ring's last computer feeds its output back to the first one with, which
+    /// is dropped by `close_input` so that the first computer can see its input closing
+    feedback: Option<Arc<Mutex<Option<Sender<Word>>>>>,
+    handles: Vec<JoinHandle<MachineResult>>,
+}
+
+impl Pipeline {
+    /// send a value to the first computer. returns false if it has already finished, or if the
+    /// pipeline's input has been closed
+    pub fn send(&self, val: Word) -> bool {
+        match &self.input {
+            Some(input) => input.send(val).is_ok(),
+            None => false,
+        }
+    }
+
+    /// stop sending values to the first computer, so that it stops with `InputBlocked` when it
+    /// next needs input. in a ring, this also stops the last computer's output being fed back,
+    /// though values it has already sent are still read
+    pub fn close_input(&mut self) {
+        self.input = None;
+        if let Some(feedback) = &self.feedback {
+            *feedback.lock().unwrap() = None;
+        }
+    }
+
+    /// close the input, then wait for every computer to finish, returning their final states in
+    /// the order they were added
+    pub fn join(mut self) -> Vec<MachineResult> {
+        self.close_input();
+        self.handles.into_iter()
+            .map(|handle| handle.join().expect("intcode thread panicked"))
+            .collect()
+    }
+}
+
+#[derive(Default)]
+pub struct PipelineBuilder {
+    computers: Vec<Computer>,
+}
+
+impl PipelineBuilder {
+    pub fn new() -> Self {
+        Self {
+            computers: Vec::new(),
+        }
+    }
+
+    pub fn machine(mut self, computer: Computer) -> Self {
+        self.computers.push(computer);
+        self
+    }
+
+    /// connect each computer's output to the next one's input
+    pub fn chain(self) -> Pipeline {
+        self.spawn(false)
+    }
+
+    /// connect each computer's output to the next one's input, and the last computer's output
+    /// back to the first one's input. the last computer's output is still also sent to the
+    /// pipeline's output
+    pub fn ring(self) -> Pipeline {
+        self.spawn(true)
+    }
+
+    fn spawn(self, ring: bool) -> Pipeline {
+        assert!(!self.computers.is_empty(), "pipeline must contain at least one computer");
+
+        let (input, first_rx) = channel();
+        let (out_tx, output) = channel();
+
+        let mut handles = Vec::new();
+        let mut feedback = None;
+        let mut rx = first_rx;
+        let last = self.computers.len() - 1;
+
+        for (i, computer) in self.computers.into_iter().enumerate() {
+            if i < last {
+                let (next_tx, next_rx) = channel();
+                handles.push(spawn(computer, rx, next_tx));
+                rx = next_rx;
+            } else if ring {
+                let first_tx = Arc::new(Mutex::new(Some(input.clone())));
+                feedback = Some(first_tx.clone());
+                let out_tx = out_tx.clone();
+                handles.push(spawn(computer, rx, move |val| {
+                    if let Some(first_tx) = &*first_tx.lock().unwrap() {
+                        let _ = first_tx.send(val);
+                    }
+                    let _ = out_tx.send(val);
+                }));
+                break;
+            } else {
+                handles.push(spawn(computer, rx, out_tx.clone()));
+                break;
+            }
+        }
+
+        Pipeline {
+            input: Some(input),
+            output,
+            feedback,
+            handles,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::from_str;
+
+    #[test]
+    fn ring_feeds_back_until_halt() {
+        // adds its setting to each input, and halts after passing on a value of 10 or more
+        let add_setting = "3,20,3,21,1,20,21,21,4,21,1007,21,10,22,1005,22,2,99";
+        let pipeline = (1..=3)
+            .fold(PipelineBuilder::new(), |builder, setting| {
+                let mut computer = Computer::new(from_str(add_setting));
+                computer.in_buf.push_back(setting);
+                builder.machine(computer)
+            })
+            .ring();
+
+        assert!(pipeline.send(0));
+        let outputs: Vec<_> = pipeline.output.iter().take(2).collect();
+        assert_eq!(outputs, [6, 12]);
+
+        let results = pipeline.join();
+        assert!(results.iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn join_closes_input() {
+        // doubles its input, then waits for more
+        let pipeline = PipelineBuilder::new()
+            .machine(Computer::new(from_str("3,9,1002,9,2,9,4,9,3,0")))
+            .chain();
+
+        assert!(pipeline.send(21));
+        assert_eq!(pipeline.output.recv(), Ok(42));
+
+        match &pipeline.join()[..] {
+            [Err((ExecError::InputBlocked, computer))] => assert_eq!(computer.pc(), 8),
+            _ => panic!("computer should be blocked on input"),
+        }
+    }
+
+    #[test]
+    fn join_stops_a_waiting_ring() {
+        // echoes its input forever, but nothing is ever sent
+        let echo = "3,7,4,7,1105,1,0,0";
+        let pipeline = PipelineBuilder::new()
+            .machine(Computer::new(from_str(echo)))
+            .machine(Computer::new(from_str(echo)))
+            .ring();
+
+        let results = pipeline.join();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| matches!(result, Err((ExecError::InputBlocked, _)))));
+    }
+}
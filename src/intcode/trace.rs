@@ -0,0 +1,250 @@
+use super::{Word, Op, Mode};
+use super::symbols::SymbolTable;
+use super::word::IntcodeWord;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::mpsc::Sender;
+
+/// an operand of a traced instruction, with the value it resolved to. for operands that are
+/// read, that's the value read; for operands that are written to, it's the address written
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct TracedOperand<W = Word> {
+    pub mode: Mode,
+    pub raw: W,
+    pub value: W,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct MemWrite<W = Word> {
+    pub addr: usize,
+    pub old: W,
+    pub new: W,
+}
+
+/// everything that happened while executing one instruction
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TraceEvent<W = Word> {
+    pub pc: usize,
+    pub op: Op,
+    pub operands: Vec<TracedOperand<W>>,
+    pub writes: Vec<MemWrite<W>>,
+    pub input: Option<W>,
+    pub output: Option<W>,
+
+    /// the relative base after the instruction executed
+    pub rel_offset: W,
+}
+
+impl<W: IntcodeWord> TraceEvent<W> {
+    pub(super) fn new(pc: usize, op: Op) -> Self {
+        Self {
+            pc,
+            op,
+            operands: Vec::new(),
+            writes: Vec::new(),
+            input: None,
+            output: None,
+            rel_offset: W::from_i64(0),
+        }
+    }
+
+    /// a single line like `12: ADD [4]=33, #3, [5]->5 ; [5] 0 -> 36`
+    pub fn to_text(&self) -> String {
+        self.to_text_with(&SymbolTable::new())
+    }
+
+    /// like `to_text`, with the pc followed by its symbol, like `12 <loop_top+2>: ADD ...`
+    pub fn to_text_with(&self, symbols: &SymbolTable) -> String {
+        let mut text = format!("{}: {}", symbols.annotate(self.pc), self.op.mnemonic());
+
+        let out_param = self.op.out_param();
+        for (i, operand) in self.operands.iter().enumerate() {
+            text.push_str(if i == 0 { " " } else { ", " });
+
+            let raw = &operand.raw;
+            match operand.mode {
+                Mode::Immediate => text.push_str(&format!("#{}", raw)),
+                Mode::Pointer => text.push_str(&format!("[{}]", raw)),
+                Mode::Relative if *raw < W::from_i64(0) => text.push_str(&format!("rb{}", raw)),
+                Mode::Relative => text.push_str(&format!("rb+{}", raw)),
+            }
+
+            match operand.mode {
+                _ if out_param == Some(i) => text.push_str(&format!("->{}", operand.value)),
+                Mode::Immediate => {}
+                _ => text.push_str(&format!("={}", operand.value)),
+            }
+        }
+
+        let mut effects = Vec::new();
+        for write in &self.writes {
+            effects.push(format!("[{}] {} -> {}", write.addr, write.old, write.new));
+        }
+        if let Some(input) = &self.input {
+            effects.push(format!("in {}", input));
+        }
+        if let Some(output) = &self.output {
+            effects.push(format!("out {}", output));
+        }
+        if self.op == Op::Off {
+            effects.push(format!("rb {}", self.rel_offset));
+        }
+
+        if !effects.is_empty() {
+            text.push_str(" ; ");
+            text.push_str(&effects.join(", "));
+        }
+
+        text
+    }
+
+    /// a single line JSON object with the same fields as this event
+    pub fn to_json(&self) -> String {
+        fn json_opt<W: fmt::Display>(val: &Option<W>) -> String {
+            val.as_ref().map(|val| val.to_string()).unwrap_or_else(|| "null".to_string())
+        }
+
+        let operands: Vec<_> = self.operands.iter()
+            .map(|operand| {
+                let mode = match operand.mode {
+                    Mode::Pointer => "pointer",
+                    Mode::Immediate => "immediate",
+                    Mode::Relative => "relative",
+                };
+                format!(r#"{{"mode":"{}","raw":{},"value":{}}}"#, mode, operand.raw, operand.value)
+            })
+            .collect();
+
+        let writes: Vec<_> = self.writes.iter()
+            .map(|write| format!(r#"{{"addr":{},"old":{},"new":{}}}"#, write.addr, write.old, write.new))
+            .collect();
+
+        format!(
+            r#"{{"pc":{},"op":"{}","operands":[{}],"writes":[{}],"input":{},"output":{},"rb":{}}}"#,
+            self.pc,
+            self.op.mnemonic(),
+            operands.join(","),
+            writes.join(","),
+            json_opt(&self.input),
+            json_opt(&self.output),
+            self.rel_offset
+        )
+    }
+}
+
+/// receives a `TraceEvent` for each instruction executed by a computer that's being traced
+pub trait TraceSink<W = Word>: Send {
+    fn record(&mut self, event: &TraceEvent<W>);
+}
+
+impl<W: IntcodeWord> TraceSink<W> for Sender<TraceEvent<W>> {
+    fn record(&mut self, event: &TraceEvent<W>) {
+        let _ = self.send(event.clone());
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum TraceFormat {
+    Text,
+    JsonLines,
+}
+
+/// writes each event as a line of text or JSON. tracing stops at the first write error, which
+/// can be checked with `error`
+pub struct TraceWriter<O> {
+    out: O,
+    format: TraceFormat,
+    symbols: SymbolTable,
+    error: Option<io::Error>,
+}
+
+impl<O: Write> TraceWriter<O> {
+    pub fn new(out: O, format: TraceFormat) -> Self {
+        Self {
+            out,
+            format,
+            symbols: SymbolTable::new(),
+            error: None,
+        }
+    }
+
+    /// name the pc in text traces using `symbols`
+    pub fn with_symbols(mut self, symbols: SymbolTable) -> Self {
+        self.symbols = symbols;
+        self
+    }
+
+    pub fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+}
+
+impl<O: Write + Send, W: IntcodeWord> TraceSink<W> for TraceWriter<O> {
+    fn record(&mut self, event: &TraceEvent<W>) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let line = match self.format {
+            TraceFormat::Text => event.to_text_with(&self.symbols),
+            TraceFormat::JsonLines => event.to_json(),
+        };
+
+        if let Err(err) = writeln!(self.out, "{}", line) {
+            self.error = Some(err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{from_str, Computer};
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn traces_each_instruction() {
+        let mut computer = Computer::new(from_str("3,9,1002,9,3,9,204,3,99,0"));
+        let (tx, rx) = channel();
+        computer.set_tracer(Some(Box::new(tx)));
+        computer.in_buf.push_back(11);
+        computer.run().unwrap();
+
+        let lines: Vec<_> = rx.try_iter().map(|event| event.to_text()).collect();
+        assert_eq!(lines, [
+            "0: IN [9]->9 ; [9] 0 -> 11, in 11",
+            "2: MUL [9]=11, #3, [9]->9 ; [9] 11 -> 33",
+            "6: OUT rb+3=9 ; out 9",
+            "8: HALT",
+        ]);
+    }
+
+    #[test]
+    fn formats_json_lines() {
+        let event: TraceEvent = TraceEvent {
+            pc: 4,
+            op: Op::Add,
+            operands: vec![
+                TracedOperand { mode: Mode::Pointer, raw: 1, value: 5 },
+                TracedOperand { mode: Mode::Immediate, raw: 2, value: 2 },
+                TracedOperand { mode: Mode::Relative, raw: -1, value: 9 },
+            ],
+            writes: vec![MemWrite { addr: 9, old: 0, new: 7 }],
+            input: None,
+            output: None,
+            rel_offset: 10,
+        };
+
+        let mut out = Vec::new();
+        TraceWriter::new(&mut out, TraceFormat::JsonLines).record(&event);
+
+        assert_eq!(String::from_utf8(out).unwrap(), concat!(
+            r#"{"pc":4,"op":"ADD","operands":["#,
+            r#"{"mode":"pointer","raw":1,"value":5},"#,
+            r#"{"mode":"immediate","raw":2,"value":2},"#,
+            r#"{"mode":"relative","raw":-1,"value":9}],"#,
+            r#""writes":[{"addr":9,"old":0,"new":7}],"input":null,"output":null,"rb":10}"#,
+            "\n",
+        ));
+    }
+}
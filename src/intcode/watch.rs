@@ -0,0 +1,275 @@
+use super::{Word, Computer};
+use super::word::IntcodeWord;
+use super::memory::PAGE_SIZE;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+/// the kind of memory access that triggers a watchpoint
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Trigger {
+    /// an instruction read an operand from a watched address
+    Read,
+
+    /// an instruction wrote to a watched address, even if the value didn't change
+    Write,
+
+    /// an instruction wrote a different value to a watched address
+    Change,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Cause {
+    Watchpoint { id: usize, trigger: Trigger },
+
+    /// an instruction wrote to the address of an instruction which has already been executed
+    CodeWrite,
+}
+
+/// a memory access that triggered a watchpoint or code write detection
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct WatchHit<W = Word> {
+    pub cause: Cause,
+
+    /// the address of the instruction that made the access
+    pub pc: usize,
+    pub addr: usize,
+
+    /// the value before and after the access, which are the same for reads
+    pub old: W,
+    pub new: W,
+}
+
+impl<W: fmt::Display> fmt::Display for WatchHit<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.cause {
+            Cause::Watchpoint { trigger: Trigger::Read, .. } => {
+                write!(f, "[{}] read by {}: {}", self.addr, self.pc, self.new)
+            }
+            Cause::Watchpoint { .. } => {
+                write!(f, "[{}] written by {}: {} -> {}", self.addr, self.pc, self.old, self.new)
+            }
+            Cause::CodeWrite => {
+                write!(f, "code at [{}] modified by {}: {} -> {}", self.addr, self.pc, self.old, self.new)
+            }
+        }
+    }
+}
+
+pub type WatchCallback<W> = Box<dyn FnMut(&WatchHit<W>) + Send>;
+
+/// what to do when a watchpoint is hit
+pub enum WatchAction<W = Word> {
+    /// stop `Computer::run_until` after the instruction that made the access
+    Stop,
+
+    /// call a function and carry on
+    Callback(WatchCallback<W>),
+}
+
+struct Watchpoint<W> {
+    id: usize,
+    addrs: Range<usize>,
+    trigger: Trigger,
+    action: WatchAction<W>,
+}
+
+/// a bit for each word in a page of memory
+type PageBits = [u64; PAGE_SIZE / 64];
+
+/// the addresses of executed instructions, one bit per word, in pages like memory so that
+/// running code at a high address doesn't need bits for every address below it
+struct Executed<W> {
+    pages: BTreeMap<usize, PageBits>,
+    action: WatchAction<W>,
+}
+
+impl<W> Executed<W> {
+    fn contains(&self, addr: usize) -> bool {
+        self.pages.get(&(addr / PAGE_SIZE))
+            .map(|bits| bits[addr % PAGE_SIZE / 64] & (1 << (addr % 64)) != 0)
+            .unwrap_or(false)
+    }
+
+    fn insert(&mut self, addr: usize) {
+        let bits = self.pages.entry(addr / PAGE_SIZE).or_insert([0; PAGE_SIZE / 64]);
+        bits[addr % PAGE_SIZE / 64] |= 1 << (addr % 64);
+    }
+}
+
+fn fire<W>(action: &mut WatchAction<W>, stop: &mut Option<WatchHit<W>>, hit: WatchHit<W>) {
+    match action {
+        // the first hit is the one reported when several happen in the same instruction
+        WatchAction::Stop => {
+            stop.get_or_insert(hit);
+        }
+        WatchAction::Callback(callback) => callback(&hit),
+    }
+}
+
+pub(super) struct Watches<W> {
+    points: Vec<Watchpoint<W>>,
+    next_id: usize,
+    executed: Option<Executed<W>>,
+
+    /// the hit that should stop execution after the current instruction
+    pub stop: Option<WatchHit<W>>,
+}
+
+impl<W: IntcodeWord> Watches<W> {
+    pub fn new() -> Self {
+        Self {
+            points: Vec::new(),
+            next_id: 0,
+            executed: None,
+            stop: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty() && self.executed.is_none()
+    }
+
+    pub fn executing(&mut self, pc: usize, size: usize) {
+        if let Some(executed) = &mut self.executed {
+            for addr in pc..pc.saturating_add(size) {
+                executed.insert(addr);
+            }
+        }
+    }
+
+    /// forget which instructions have been executed, because memory has been replaced
+    pub fn reset_executed(&mut self) {
+        if let Some(executed) = &mut self.executed {
+            executed.pages.clear();
+        }
+    }
+
+    pub fn read(&mut self, pc: usize, addr: usize, val: &W) {
+        for point in &mut self.points {
+            if point.trigger == Trigger::Read && point.addrs.contains(&addr) {
+                let cause = Cause::Watchpoint { id: point.id, trigger: Trigger::Read };
+                let hit = WatchHit { cause, pc, addr, old: val.clone(), new: val.clone() };
+                fire(&mut point.action, &mut self.stop, hit);
+            }
+        }
+    }
+
+    pub fn write(&mut self, pc: usize, addr: usize, old: &W, new: &W) {
+        for point in &mut self.points {
+            let triggered = match point.trigger {
+                Trigger::Read => false,
+                Trigger::Write => true,
+                Trigger::Change => old != new,
+            };
+
+            if triggered && point.addrs.contains(&addr) {
+                let cause = Cause::Watchpoint { id: point.id, trigger: point.trigger };
+                let (old, new) = (old.clone(), new.clone());
+                fire(&mut point.action, &mut self.stop, WatchHit { cause, pc, addr, old, new });
+            }
+        }
+
+        if let Some(executed) = &mut self.executed {
+            if executed.contains(addr) {
+                let (old, new) = (old.clone(), new.clone());
+                let hit = WatchHit { cause: Cause::CodeWrite, pc, addr, old, new };
+                fire(&mut executed.action, &mut self.stop, hit);
+            }
+        }
+    }
+}
+
+impl<W: IntcodeWord> Computer<W> {
+    /// watch the memory at `addrs` for accesses by the program, returning an id for the
+    /// watchpoint. reads and writes made with `mem_load` and `mem_store` don't trigger
+    /// watchpoints
+    pub fn add_watchpoint(&mut self, addrs: Range<usize>, trigger: Trigger, action: WatchAction<W>)
+        -> usize
+    {
+        let id = self.watches.next_id;
+        self.watches.next_id += 1;
+        self.watches.points.push(Watchpoint { id, addrs, trigger, action });
+        id
+    }
+
+    pub fn remove_watchpoint(&mut self, id: usize) -> bool {
+        let len = self.watches.points.len();
+        self.watches.points.retain(|point| point.id != id);
+        self.watches.points.len() != len
+    }
+
+    /// each watchpoint's id, addresses and trigger, in the order they were added
+    pub fn watchpoints(&self) -> impl Iterator<Item=(usize, Range<usize>, Trigger)> + '_ {
+        self.watches.points.iter().map(|point| (point.id, point.addrs.clone(), point.trigger))
+    }
+
+    /// detect the program modifying its own code, by checking whether each write lands on an
+    /// instruction that has already been executed. only instructions executed from now on are
+    /// tracked, and restoring a snapshot forgets them. `None` turns detection off
+    pub fn detect_code_writes(&mut self, action: Option<WatchAction<W>>) {
+        self.watches.executed = action.map(|action| Executed { pages: BTreeMap::new(), action });
+    }
+
+    /// the watchpoint or code write that stopped the last instruction executed, if any. this is
+    /// how a watch with `WatchAction::Stop` is seen when stepping one instruction at a time
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit<W>> {
+        self.watches.stop.take()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{StopReason, asm::assemble};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn stops_and_calls_back() {
+        let code = assemble("
+                    ADD [x], #1, [x]
+                    ADD [x], #0, [x]
+                    ADD #0, #0, [0]
+                    HALT
+            x:      db 0
+        ").unwrap();
+
+        let mut computer = Computer::new(code);
+        computer.add_watchpoint(13..14, Trigger::Change, WatchAction::Stop);
+        computer.detect_code_writes(Some(WatchAction::Stop));
+
+        let reads = Arc::new(Mutex::new(Vec::new()));
+        let log = reads.clone();
+        let reader = computer.add_watchpoint(0..20, Trigger::Read, WatchAction::Callback(Box::new(move |hit| {
+            log.lock().unwrap().push((hit.pc, hit.addr));
+        })));
+
+        let cause = Cause::Watchpoint { id: 0, trigger: Trigger::Change };
+        let hit = WatchHit { cause, pc: 0, addr: 13, old: 0, new: 1 };
+        assert_eq!(computer.run_until(None), Ok(StopReason::WatchpointHit(hit)));
+
+        // writing the same value back isn't a change, so the next stop is the code being modified
+        let hit = WatchHit { cause: Cause::CodeWrite, pc: 8, addr: 0, old: 1001, new: 0 };
+        assert_eq!(computer.run_until(None), Ok(StopReason::WatchpointHit(hit)));
+        assert_eq!(hit.to_string(), "code at [0] modified by 8: 1001 -> 0");
+
+        assert_eq!(computer.run_until(None), Ok(StopReason::Halted));
+        assert_eq!(*reads.lock().unwrap(), [(0, 13), (4, 13)]);
+        assert!(computer.remove_watchpoint(reader));
+        assert!(!computer.remove_watchpoint(reader));
+    }
+
+    #[test]
+    fn detects_code_writes_far_into_memory() {
+        // jumps to code far into memory, which overwrites itself
+        let far = 1 << 60;
+        let mut computer = Computer::new(vec![1105, 1, far as Word]);
+        for (offset, word) in [1101, 0, 0, far as Word, 99].iter().enumerate() {
+            computer.mem_store(far + offset, *word);
+        }
+        computer.detect_code_writes(Some(WatchAction::Stop));
+
+        let hit = WatchHit { cause: Cause::CodeWrite, pc: far, addr: far, old: 1101, new: 0 };
+        assert_eq!(computer.run_until(None), Ok(StopReason::WatchpointHit(hit)));
+    }
+}
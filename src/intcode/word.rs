@@ -0,0 +1,178 @@
+//! the types a computer's memory can hold. programs use the same instructions whatever the word
+//! type, but it decides how big the numbers they work with can get and what happens when a result
+//! is too big
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::Hash;
+use std::num::ParseIntError;
+use std::str::FromStr;
+use num::{BigInt, ToPrimitive};
+
+pub trait IntcodeWord: Clone + Ord + Hash + fmt::Debug + fmt::Display + FromStr + Send + 'static {
+    fn from_i64(val: i64) -> Self;
+
+    /// `None` if the value doesn't fit in an `i64`, which can't be a valid opcode or address
+    fn to_i64(&self) -> Option<i64>;
+
+    /// the sum, or `None` if it overflows
+    fn add(&self, rhs: &Self) -> Option<Self>;
+
+    /// the product, or `None` if it overflows
+    fn mul(&self, rhs: &Self) -> Option<Self>;
+
+    fn is_zero(&self) -> bool {
+        self.to_i64() == Some(0)
+    }
+}
+
+/// wraps on overflow, in debug builds too
+impl IntcodeWord for i64 {
+    fn from_i64(val: i64) -> Self {
+        val
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(*self)
+    }
+
+    fn add(&self, rhs: &Self) -> Option<Self> {
+        Some(self.wrapping_add(*rhs))
+    }
+
+    fn mul(&self, rhs: &Self) -> Option<Self> {
+        Some(self.wrapping_mul(*rhs))
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+}
+
+/// wraps on overflow, in debug builds too
+impl IntcodeWord for i128 {
+    fn from_i64(val: i64) -> Self {
+        val as i128
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        i64::try_from(*self).ok()
+    }
+
+    fn add(&self, rhs: &Self) -> Option<Self> {
+        Some(self.wrapping_add(*rhs))
+    }
+
+    fn mul(&self, rhs: &Self) -> Option<Self> {
+        Some(self.wrapping_mul(*rhs))
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+}
+
+/// never overflows, at the cost of an allocation for every word that doesn't fit in a machine word
+impl IntcodeWord for BigInt {
+    fn from_i64(val: i64) -> Self {
+        BigInt::from(val)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        ToPrimitive::to_i64(self)
+    }
+
+    fn add(&self, rhs: &Self) -> Option<Self> {
+        Some(self + rhs)
+    }
+
+    fn mul(&self, rhs: &Self) -> Option<Self> {
+        Some(self * rhs)
+    }
+}
+
+/// an `i64` which stops the computer with `ExecError::Overflow` instead of overflowing
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Checked(pub i64);
+
+impl fmt::Display for Checked {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Checked {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Checked)
+    }
+}
+
+impl IntcodeWord for Checked {
+    fn from_i64(val: i64) -> Self {
+        Checked(val)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(self.0)
+    }
+
+    fn add(&self, rhs: &Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Checked)
+    }
+
+    fn mul(&self, rhs: &Self) -> Option<Self> {
+        self.0.checked_mul(rhs.0).map(Checked)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{Computer, ExecError, from_str_as};
+
+    fn run<W: IntcodeWord>(code: &str) -> Result<Vec<W>, ExecError<W>> {
+        let mut computer = Computer::new(from_str_as(code));
+        computer.run()?;
+        Ok(computer.out_buf.into())
+    }
+
+    fn run_day9_samples<W: IntcodeWord>() {
+        let big = W::from_i64(1125899906842624);
+        assert_eq!(run("104,1125899906842624,99"), Ok(vec![big]));
+
+        let product = W::from_i64(1219070632396864);
+        assert_eq!(run("1102,34915192,34915192,7,4,7,99,0"), Ok(vec![product]));
+
+        let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        assert_eq!(run(quine), Ok(from_str_as::<W>(quine)));
+    }
+
+    #[test]
+    fn runs_day9_samples_with_every_word() {
+        run_day9_samples::<i64>();
+        run_day9_samples::<i128>();
+        run_day9_samples::<BigInt>();
+        run_day9_samples::<Checked>();
+    }
+
+    #[test]
+    fn overflow_depends_on_word() {
+        // squares 2^40
+        let code = "1102,1099511627776,1099511627776,7,4,7,99,0";
+        let product = "1208925819614629174706176";
+
+        assert_eq!(run::<i64>(code), Ok(vec![0]));
+        assert_eq!(run::<i128>(code), Ok(vec![product.parse().unwrap()]));
+        assert_eq!(run::<BigInt>(code), Ok(vec![product.parse().unwrap()]));
+
+        let err = run::<Checked>(code).unwrap_err();
+        assert_eq!(err, ExecError::Overflow { pc: 0, word: Checked(1102) });
+        assert_eq!(err.to_string(), "overflow in opcode 1102 at 0");
+    }
+}
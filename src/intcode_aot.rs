@@ -0,0 +1,12 @@
+mod intcode;
+use std::env;
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let usage = "usage: intcode-aot <program file> <function name>";
+    let path = args.next().expect(usage);
+    let name = args.next().expect(usage);
+
+    let code = intcode::load_file(&path).unwrap_or_else(|err| panic!("failed to load {}: {}", path, err));
+    print!("{}", intcode::aot::transpile(&code, &name));
+}
@@ -0,0 +1,82 @@
+mod intcode;
+use intcode::{Computer, ExecError};
+use std::env;
+use std::io::{self, BufRead, Write};
+
+/// run the program, giving it a line of `input` each time it waits for input. text it outputs is
+/// written as it is, and other values are written on lines of their own
+fn interact(computer: &mut Computer, input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+    let mut lines = input.lines();
+    loop {
+        let result = computer.run();
+
+        let output = computer.take_ascii_output();
+        write!(out, "{}", output.text)?;
+        for value in output.values {
+            writeln!(out, "{}", value)?;
+        }
+        out.flush()?;
+
+        match result {
+            Err(ExecError::InputBlocked) => match lines.next() {
+                Some(line) => computer.push_line(line?.trim_end_matches('\r')),
+                None => break Ok(()),
+            },
+            Ok(()) => break Ok(()),
+            Err(err) => {
+                writeln!(out, "stopped: {}", err)?;
+                break Ok(());
+            }
+        }
+    }
+}
+
+fn main() {
+    let path = env::args().nth(1).expect("usage: intcode-ascii <program file>");
+    let mut computer = intcode::load_computer(&path)
+        .unwrap_or_else(|err| panic!("failed to load {}: {}", path, err));
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    interact(&mut computer, stdin.lock(), stdout.lock()).expect("i/o error");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use intcode::asm::assemble;
+
+    #[test]
+    fn answers_each_line() {
+        // prints a prompt, then echoes lines until an empty one, which it answers with 100 times
+        // the total length of the others
+        let code = assemble("
+                    OUT #62
+                    OUT #32
+            loop:   IN [c]
+                    EQ [c], #10, [done]
+                    JNZ [done], eol
+                    OUT [c]
+                    ADD [n], #1, [n]
+                    ADD [len], #1, [len]
+                    JZ #0, loop
+            eol:    JZ [len], end
+                    OUT #10
+                    ADD #0, #0, [len]
+                    JZ #0, loop
+            end:    MUL [n], #100, [n]
+                    OUT [n]
+                    HALT
+            c:      db 0
+            n:      db 0
+            len:    db 0
+            done:   db 0
+        ").unwrap();
+
+        let mut computer = Computer::new(code);
+        let mut out = Vec::new();
+        interact(&mut computer, "hi\r\nthere\n\nunread\n".as_bytes(), &mut out).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "> hi\nthere\n700\n");
+    }
+}
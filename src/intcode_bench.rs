@@ -0,0 +1,126 @@
+use itertools::*;
+mod intcode;
+use intcode::{Word, Computer, StopReason};
+use intcode::asm::assemble;
+use std::time::{Duration, Instant};
+
+/// counts down from a million, so that the decode cost of a tight loop dominates
+const COUNTDOWN: &str = "
+    loop:   ADD [n], #-1, [n]
+            LT #0, [n], [more]
+            JNZ [more], loop
+            OUT [n]
+            HALT
+    n:      db 1000000
+    more:   db 0
+";
+
+struct Workload {
+    name: &'static str,
+    run: fn(&[Word], bool),
+}
+
+fn new_computer(code: &[Word], cache: bool) -> Computer {
+    let mut computer = Computer::new(code.to_vec());
+    computer.set_decode_cache(cache);
+    computer
+}
+
+/// like day 2: search every noun and verb, restoring the program between runs
+fn noun_verb_search(code: &[Word], cache: bool) {
+    let mut computer = new_computer(code, cache);
+    let initial = computer.snapshot();
+
+    for noun in 0..100 {
+        for verb in 0..100 {
+            computer.restore(&initial);
+            computer.mem_store(1, noun);
+            computer.mem_store(2, verb);
+            let _ = computer.run();
+        }
+    }
+}
+
+/// like days 5 and 9: run once for each of the inputs used by the puzzles
+fn run_diagnostics(code: &[Word], cache: bool) {
+    for input in 1..=5 {
+        let mut computer = new_computer(code, cache);
+        computer.in_buf.push_back(input);
+        let _ = computer.run();
+    }
+}
+
+/// like day 7: run every feedback loop of amplifiers on one thread, passing signals around
+fn amplifier_loops(code: &[Word], cache: bool) {
+    for setting in (5 as Word..=9).permutations(5) {
+        let mut amps: Vec<_> = setting.iter()
+            .map(|setting| {
+                let mut amp = new_computer(code, cache);
+                amp.in_buf.push_back(*setting);
+                amp
+            })
+            .collect();
+
+        let mut signal = 0;
+        'feedback: loop {
+            for amp in &mut amps {
+                amp.in_buf.push_back(signal);
+                match amp.run_until(None) {
+                    Ok(StopReason::OutputProduced) => signal = amp.out_buf.pop_front().unwrap(),
+                    _ => break 'feedback,
+                }
+            }
+        }
+    }
+}
+
+fn run_once(code: &[Word], cache: bool) {
+    let _ = new_computer(code, cache).run();
+}
+
+/// the fastest of several runs, which is the least affected by whatever else the machine is doing
+fn time(workload: &Workload, code: &[Word], cache: bool) -> Duration {
+    const REPEATS: usize = 10;
+
+    (0..REPEATS)
+        .map(|_| {
+            let start = Instant::now();
+            (workload.run)(code, cache);
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let countdown = assemble(COUNTDOWN).expect("countdown should assemble");
+    let countdown = countdown.iter().map(Word::to_string).join(",");
+
+    let workloads = [
+        (Workload { name: "day2", run: noun_verb_search }, include_str!("day2.txt")),
+        (Workload { name: "day5", run: run_diagnostics }, include_str!("day5.txt")),
+        (Workload { name: "day7", run: amplifier_loops }, include_str!("day7.txt")),
+        (Workload { name: "day9", run: run_diagnostics }, include_str!("day9.txt")),
+        (Workload { name: "countdown", run: run_once }, countdown.as_str()),
+    ];
+
+    println!("{:<10} {:>12} {:>12} {:>8}", "workload", "uncached", "cached", "speedup");
+    for (workload, input) in workloads.iter() {
+        if input.trim().is_empty() {
+            println!("{:<10} (no puzzle input)", workload.name);
+            continue;
+        }
+
+        let code = intcode::from_str(input.trim());
+        let uncached = time(workload, &code, false);
+        let cached = time(workload, &code, true);
+
+        println!(
+            "{:<10} {:>10.2}ms {:>10.2}ms {:>7.2}x",
+            workload.name,
+            uncached.as_secs_f64() * 1000.0,
+            cached.as_secs_f64() * 1000.0,
+            uncached.as_secs_f64() / cached.as_secs_f64()
+        );
+    }
+}
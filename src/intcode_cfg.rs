@@ -0,0 +1,9 @@
+mod intcode;
+use std::env;
+
+fn main() {
+    let path = env::args().nth(1).expect("usage: intcode-cfg <program file>");
+    let code = intcode::load_file(&path).unwrap_or_else(|err| panic!("failed to load {}: {}", path, err));
+
+    print!("{}", intcode::cfg::build(&code).to_dot());
+}
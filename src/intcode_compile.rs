@@ -0,0 +1,34 @@
+mod intcode;
+use intcode::lang;
+use std::env;
+use std::fs;
+
+fn main() {
+    let usage = "usage: intcode-compile [--asm] <source file> [symbol file]";
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let asm = match args.iter().position(|arg| arg == "--asm") {
+        Some(pos) => {
+            args.remove(pos);
+            true
+        }
+        None => false,
+    };
+
+    let mut args = args.into_iter();
+    let path = args.next().expect(usage);
+    let source = fs::read_to_string(&path).expect("failed to read source");
+
+    let fail = |err: lang::CompileError| -> ! { panic!("{}:{}", path, err) };
+    if asm {
+        print!("{}", lang::compile_to_asm(&source).unwrap_or_else(|err| fail(err)));
+        return;
+    }
+
+    let (code, symbols) = lang::compile_with_symbols(&source).unwrap_or_else(|err| fail(err));
+    let words: Vec<_> = code.iter().map(|word| word.to_string()).collect();
+    println!("{}", words.join(","));
+
+    if let Some(symbol_path) = args.next() {
+        symbols.save(&symbol_path).unwrap_or_else(|err| panic!("failed to write {}: {}", symbol_path, err));
+    }
+}
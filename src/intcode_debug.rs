@@ -0,0 +1,482 @@
+mod intcode;
+use intcode::{Word, Computer, ExecError, Step, disasm};
+use intcode::symbols::SymbolTable;
+use intcode::watch::{Trigger, WatchAction};
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+const HELP: &str = "\
+commands:
+    break <addr>, b         stop before executing the instruction at addr
+    delete <addr>, d        remove a breakpoint
+    watch <addr>, w         stop after an instruction changes the value at addr
+    rwatch <addr>           stop after an instruction reads the value at addr
+    unwatch <addr>          remove the watches on addr
+    smc                     stop when the program writes to code it has executed (toggle)
+    info                    list breakpoints and watches
+    step [n], s             execute n instructions (default 1)
+    continue, c             run until halt, input is needed, or a breakpoint or watch is hit
+    record [limit]          start recording so execution can be reversed
+    back [n]                undo n recorded instructions (default 1)
+    back-to <addr>          undo recorded instructions until the pc is addr
+    regs, r                 print the pc and relative base
+    dump <addr> [len], x    print len words of memory starting at addr (default 8)
+    disas [n], dis          disassemble n instructions starting at the pc (default 5)
+    input <val>..., i       push values to the input buffer
+    symbols <file>          load a symbol table, so addresses can be given as names like loop+3
+    quit, q                 exit the debugger";
+
+struct Debugger {
+    computer: Computer,
+
+    /// whether writes to executed code stop execution
+    code_writes: bool,
+
+    symbols: SymbolTable,
+}
+
+/// the most words `dump` and `disas` read at once
+const MAX_WORDS: usize = 1 << 16;
+
+/// the addresses of `len` words from `start`
+fn word_range(start: usize, len: usize) -> Result<Range<usize>, String> {
+    if len > MAX_WORDS {
+        return Err(format!("can't read more than {} words", MAX_WORDS));
+    }
+
+    match start.checked_add(len) {
+        Some(end) => Ok(start..end),
+        None => Err(format!("{} words from {} is past the end of memory", len, start)),
+    }
+}
+
+/// the range a watchpoint on the single word at `addr` covers
+fn watch_range(addr: usize) -> Result<Range<usize>, String> {
+    match addr.checked_add(1) {
+        Some(end) => Ok(addr..end),
+        None => Err(format!("can't watch {}, the last address", addr)),
+    }
+}
+
+fn parse_num<T: std::str::FromStr>(arg: Option<&str>) -> Result<T, String> {
+    let arg = arg.ok_or_else(|| "missing argument".to_string())?;
+    arg.parse().map_err(|_| format!("bad number: {}", arg))
+}
+
+impl Debugger {
+    fn new(computer: Computer) -> Self {
+        Self {
+            computer,
+            code_writes: false,
+            symbols: SymbolTable::new(),
+        }
+    }
+
+    /// a number or a symbol, with an optional offset
+    fn parse_addr(&self, arg: Option<&str>) -> Result<usize, String> {
+        let arg = arg.ok_or_else(|| "missing argument".to_string())?;
+        self.symbols.resolve(arg).ok_or_else(|| format!("bad address: {}", arg))
+    }
+
+    /// execute a single command line, returning false if the debugger should exit
+    fn exec(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let mut args = line.split_whitespace();
+        let cmd = match args.next() {
+            Some(cmd) => cmd,
+            None => return Ok(true),
+        };
+
+        let result = match cmd {
+            "break" | "b" => self.parse_addr(args.next()).map(|addr| {
+                self.computer.add_breakpoint(addr);
+                format!("breakpoint at {}", self.symbols.annotate(addr))
+            }),
+
+            "delete" | "d" => self.parse_addr(args.next()).map(|addr| {
+                if self.computer.remove_breakpoint(addr) {
+                    format!("deleted breakpoint at {}", addr)
+                } else {
+                    format!("no breakpoint at {}", addr)
+                }
+            }),
+
+            "watch" | "w" => self.parse_addr(args.next()).and_then(|addr| {
+                let addrs = watch_range(addr)?;
+                self.computer.add_watchpoint(addrs, Trigger::Change, WatchAction::Stop);
+                Ok(format!("watching [{}] = {}", addr, self.computer.mem_load(addr)))
+            }),
+
+            "rwatch" => self.parse_addr(args.next()).and_then(|addr| {
+                let addrs = watch_range(addr)?;
+                self.computer.add_watchpoint(addrs, Trigger::Read, WatchAction::Stop);
+                Ok(format!("watching reads of [{}]", addr))
+            }),
+
+            "unwatch" => self.parse_addr(args.next()).map(|addr| {
+                let ids: Vec<_> = self.computer.watchpoints()
+                    .filter(|(_, addrs, _)| addrs.contains(&addr))
+                    .map(|(id, _, _)| id)
+                    .collect();
+
+                if ids.is_empty() {
+                    format!("not watching [{}]", addr)
+                } else {
+                    for id in ids {
+                        self.computer.remove_watchpoint(id);
+                    }
+                    format!("stopped watching [{}]", addr)
+                }
+            }),
+
+            "smc" => {
+                self.code_writes = !self.code_writes;
+                if self.code_writes {
+                    self.computer.detect_code_writes(Some(WatchAction::Stop));
+                    Ok("stopping on writes to executed code".to_string())
+                } else {
+                    self.computer.detect_code_writes(None);
+                    Ok("not stopping on writes to executed code".to_string())
+                }
+            }
+
+            "info" => {
+                let mut breakpoints: Vec<_> = self.computer.breakpoints().collect();
+                breakpoints.sort();
+                for addr in breakpoints {
+                    writeln!(out, "breakpoint at {}", self.symbols.annotate(addr))?;
+                }
+                for (_, addrs, trigger) in self.computer.watchpoints() {
+                    let addr = addrs.start;
+                    match trigger {
+                        Trigger::Read => writeln!(out, "watching reads of [{}]", addr)?,
+                        _ => writeln!(out, "watching [{}] = {}", addr, self.computer.mem_load(addr))?,
+                    }
+                }
+                if self.code_writes {
+                    writeln!(out, "stopping on writes to executed code")?;
+                }
+                return Ok(true);
+            }
+
+            "step" | "s" => match args.next() {
+                Some(count) => parse_num(Some(count)),
+                None => Ok(1),
+            }.map(|count| self.resume(Some(count))),
+
+            "continue" | "c" => Ok(self.resume(None)),
+
+            "record" => match args.next() {
+                Some(limit) => parse_num(Some(limit)).map(Some),
+                None => Ok(None),
+            }.map(|limit| {
+                self.computer.start_recording(limit);
+                "recording".to_string()
+            }),
+
+            "back" => match args.next() {
+                Some(count) => parse_num(Some(count)),
+                None => Ok(1),
+            }.and_then(|count| self.reverse(|computer| {
+                let mut undone = 0;
+                while undone < count && computer.step_back() {
+                    undone += 1;
+                }
+                format!("stepped back {}", undone)
+            })),
+
+            "back-to" => self.parse_addr(args.next()).and_then(|addr| self.reverse(|computer| {
+                if computer.run_back_until(addr) {
+                    format!("reversed to {}", addr)
+                } else {
+                    "reached start of history".to_string()
+                }
+            })),
+
+            "regs" | "r" => Ok(format!(
+                "pc={} rb={}",
+                self.computer.pc(),
+                self.computer.rel_offset()
+            )),
+
+            "dump" | "x" => self.parse_addr(args.next()).and_then(|addr| {
+                let len = match args.next() {
+                    Some(len) => parse_num(Some(len))?,
+                    None => 8,
+                };
+
+                let words: Vec<_> = word_range(addr, len)?
+                    .map(|addr| self.computer.mem_load(addr).to_string())
+                    .collect();
+                Ok(format!("{}: {}", addr, words.join(",")))
+            }),
+
+            "disas" | "dis" => match args.next() {
+                Some(count) => parse_num(Some(count)),
+                None => Ok(5),
+            }.and_then(|count| self.disassemble(count)),
+
+            "input" | "i" => args
+                .map(|arg| parse_num(Some(arg)))
+                .collect::<Result<Vec<Word>, _>>()
+                .map(|vals| {
+                    self.computer.in_buf.extend(&vals);
+                    format!("{} values waiting for input", self.computer.in_buf.len())
+                }),
+
+            "symbols" => match args.next() {
+                Some(path) => SymbolTable::load(path).map(|symbols| {
+                    self.symbols = symbols;
+                    format!("loaded {} symbols", self.symbols.symbols().len())
+                }).map_err(|err| format!("failed to load {}: {}", path, err)),
+                None => Err("missing argument".to_string()),
+            },
+
+            "help" | "h" => Ok(HELP.to_string()),
+
+            "quit" | "q" => return Ok(false),
+
+            _ => Err(format!("unknown command: {} (try help)", cmd)),
+        };
+
+        match result {
+            Ok(msg) => writeln!(out, "{}", msg)?,
+            Err(msg) => writeln!(out, "error: {}", msg)?,
+        }
+
+        Ok(true)
+    }
+
+    fn resume(&mut self, step_limit: Option<u64>) -> String {
+        let mut steps = 0;
+        let mut outputs = Vec::new();
+
+        let reason = loop {
+            if step_limit.map(|limit| steps >= limit).unwrap_or(false) {
+                break format!("stepped {}", steps);
+            }
+
+            if steps > 0 && self.computer.has_breakpoint(self.computer.pc()) {
+                break format!("breakpoint at {}", self.symbols.annotate(self.computer.pc()));
+            }
+
+            match self.computer.step() {
+                Ok(Step::Halted) => break "halted".to_string(),
+                Ok(Step::OutputProduced) => outputs.extend(self.computer.out_buf.drain(..)),
+                Ok(Step::Executed) => {}
+                Err(ExecError::InputBlocked) => break "waiting for input".to_string(),
+                Err(err) => break format!("error: {}", err),
+            }
+            steps += 1;
+
+            if let Some(hit) = self.computer.take_watch_hit() {
+                break format!("watch {}", hit);
+            }
+        };
+
+        let mut msg = String::new();
+        for val in outputs {
+            msg.push_str(&format!("output: {}\n", val));
+        }
+        msg.push_str(&reason);
+        msg.push('\n');
+        msg.push_str(&self.disassemble(1).unwrap_or_else(|err| format!("error: {}", err)));
+        msg
+    }
+
+    /// undo recorded instructions with `undo`, which returns a description of how far it went
+    fn reverse(&mut self, undo: impl FnOnce(&mut Computer) -> String) -> Result<String, String> {
+        if self.computer.history().is_none() {
+            return Err("not recording (try record)".to_string());
+        }
+
+        let mut msg = undo(&mut self.computer);
+        msg.push('\n');
+        msg.push_str(&self.disassemble(1)?);
+        Ok(msg)
+    }
+
+    fn disassemble(&self, count: usize) -> Result<String, String> {
+        let pc = self.computer.pc();
+
+        // enough words for `count` of the longest instructions
+        let words: Vec<_> = word_range(pc, count.saturating_mul(4))?
+            .map(|addr| self.computer.mem_load(addr))
+            .collect();
+
+        let mut lines = Vec::new();
+        let mut offset = 0;
+        while lines.len() < count {
+            let (text, len) = match disasm::decode_at(&words, offset) {
+                Some(instruction) => (instruction.to_string(), instruction.size()),
+                None => (format!("db {}", words[offset]), 1),
+            };
+
+            let marker = if offset == 0 { "=>" } else { "  " };
+            lines.push(format!("{} {}: {}", marker, self.symbols.annotate(pc + offset), text));
+            offset += len;
+        }
+
+        Ok(lines.join("\n"))
+    }
+}
+
+fn run_script(debugger: &mut Debugger, input: impl BufRead, mut out: impl Write, prompt: bool)
+    -> io::Result<()>
+{
+    let mut lines = input.lines();
+    loop {
+        if prompt {
+            write!(out, "(icdb) ")?;
+            out.flush()?;
+        }
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break Ok(()),
+        };
+
+        if !debugger.exec(&line, &mut out)? {
+            break Ok(());
+        }
+    }
+}
+
+fn main() {
+    let usage = "usage: intcode-debug <program file> [symbol file]";
+    let mut args = env::args().skip(1);
+    let path = args.next().expect(usage);
+    let computer = intcode::load_computer(&path)
+        .unwrap_or_else(|err| panic!("failed to load {}: {}", path, err));
+
+    let mut debugger = Debugger::new(computer);
+    if let Some(path) = args.next() {
+        debugger.symbols = SymbolTable::load(&path)
+            .unwrap_or_else(|err| panic!("failed to load {}: {}", path, err));
+    }
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    run_script(&mut debugger, stdin.lock(), stdout.lock(), true).expect("i/o error");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(code: &str, script: &str) -> String {
+        let mut debugger = Debugger::new(Computer::new(intcode::from_str(code)));
+        let mut out = Vec::new();
+        run_script(&mut debugger, script.as_bytes(), &mut out, false).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn breaks_and_steps() {
+        let out = run("3,11,1001,11,5,11,4,11,1105,1,0,0", "\
+            break 6\n\
+            c\n\
+            input 10\n\
+            c\n\
+            regs\n\
+            step\n\
+            x 11 1\n");
+
+        assert_eq!(out, "\
+            breakpoint at 6\n\
+            waiting for input\n\
+            => 0: IN [11]\n\
+            1 values waiting for input\n\
+            breakpoint at 6\n\
+            => 6: OUT [11]\n\
+            pc=6 rb=0\n\
+            output: 15\n\
+            stepped 1\n\
+            => 8: JNZ #1, #0\n\
+            11: 15\n");
+    }
+
+    #[test]
+    fn uses_symbols() {
+        let mut debugger = Debugger::new(Computer::new(intcode::from_str("3,11,1001,11,5,11,4,11,1105,1,0,0")));
+        debugger.symbols = SymbolTable::from_text("label start 0\nlabel print 6\nvar n 11 1").unwrap();
+
+        let mut out = Vec::new();
+        run_script(&mut debugger, "b print\ni 1\nc\nx n 1\nb nowhere\n".as_bytes(), &mut out, false).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+            breakpoint at 6 <print>\n\
+            1 values waiting for input\n\
+            breakpoint at 6 <print>\n\
+            => 6 <print>: OUT [11]\n\
+            11: 6\n\
+            error: bad address: nowhere\n");
+    }
+
+    #[test]
+    fn stops_on_watch() {
+        let out = run("1101,2,3,10,1101,4,5,11,99,0,0,0", "watch 11\ncontinue\ndis 2\nq\nregs\n");
+
+        let expected = [
+            "watching [11] = 0",
+            "watch [11] written by 4: 0 -> 9",
+            "=> 8: HALT",
+            "=> 8: HALT",
+            "   9: db 0",
+        ];
+        assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn steps_backwards() {
+        let out = run("1101,2,3,10,1101,4,5,11,99,0,0,0", "\
+            back\n\
+            record\n\
+            c\n\
+            back 2\n\
+            x 10 2\n\
+            back-to 0\n");
+
+        let expected = [
+            "error: not recording (try record)",
+            "recording",
+            "halted",
+            "=> 8: HALT",
+            "stepped back 2",
+            "=> 4: ADD #4, #5, [11]",
+            "10: 5,0",
+            "reversed to 0",
+            "=> 0: ADD #2, #3, [10]",
+        ];
+        assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn rejects_huge_ranges() {
+        let out = run("99", "x 18446744073709551615 2
+x 0 100000
+dis 18446744073709551615
+x 0 1
+");
+
+        let expected = [
+            "error: 2 words from 18446744073709551615 is past the end of memory",
+            "error: can't read more than 65536 words",
+            "error: can't read more than 65536 words",
+            "0: 99",
+        ];
+        assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn rejects_watching_the_last_address() {
+        let out = run("99", "watch 18446744073709551615\nrwatch 18446744073709551615\n");
+
+        let expected = [
+            "error: can't watch 18446744073709551615, the last address",
+            "error: can't watch 18446744073709551615, the last address",
+        ];
+        assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+    }
+}
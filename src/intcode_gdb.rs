@@ -0,0 +1,46 @@
+mod intcode;
+use intcode::{Word, Computer, gdb};
+use std::env;
+use std::fs;
+use std::net::TcpListener;
+
+fn main() {
+    let usage = "usage: intcode-gdb <program file> <port | unix socket path> [input]...";
+    let mut args = env::args().skip(1);
+    let path = args.next().expect(usage);
+    let listen = args.next().expect(usage);
+
+    let mut computer = intcode::load_computer(&path)
+        .unwrap_or_else(|err| panic!("failed to load {}: {}", path, err));
+    computer.in_buf.extend(args.map(|arg| arg.parse::<Word>().expect(usage)));
+
+    // a number is a tcp port on the loopback interface, anything else a unix socket
+    if let Ok(port) = listen.parse::<u16>() {
+        let listener = TcpListener::bind(("127.0.0.1", port)).expect("failed to listen");
+        eprintln!("listening on {}", listener.local_addr().unwrap());
+
+        let (stream, _) = listener.accept().expect("failed to accept connection");
+        stream.set_nodelay(true).expect("failed to set nodelay");
+        gdb::serve(&mut computer, stream).expect("i/o error");
+    } else {
+        serve_unix(&mut computer, &listen);
+    }
+}
+
+#[cfg(unix)]
+fn serve_unix(computer: &mut Computer, path: &str) {
+    use std::os::unix::net::UnixListener;
+
+    let listener = UnixListener::bind(path).expect("failed to listen");
+    eprintln!("listening on {}", path);
+
+    let (stream, _) = listener.accept().expect("failed to accept connection");
+    let result = gdb::serve(computer, stream);
+    let _ = fs::remove_file(path);
+    result.expect("i/o error");
+}
+
+#[cfg(not(unix))]
+fn serve_unix(_computer: &mut Computer, _path: &str) {
+    panic!("unix sockets aren't supported on this platform");
+}
@@ -0,0 +1,46 @@
+mod intcode;
+use intcode::symbols::SymbolTable;
+use std::env;
+
+/// the number of instructions and addresses listed in the table
+const TABLE_ROWS: usize = 20;
+
+fn main() {
+    let usage = "usage: intcode-profile [--folded] [--symbols <file>] <program file> [input]...";
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let folded = match args.iter().position(|arg| arg == "--folded") {
+        Some(pos) => {
+            args.remove(pos);
+            true
+        }
+        None => false,
+    };
+
+    let symbols = match args.iter().position(|arg| arg == "--symbols") {
+        Some(pos) if pos + 1 < args.len() => {
+            let path = args.remove(pos + 1);
+            args.remove(pos);
+            SymbolTable::load(&path).unwrap_or_else(|err| panic!("failed to load {}: {}", path, err))
+        }
+        Some(_) => panic!("{}", usage),
+        None => SymbolTable::new(),
+    };
+
+    let mut args = args.into_iter();
+    let path = args.next().expect(usage);
+    let mut computer = intcode::load_computer(&path)
+        .unwrap_or_else(|err| panic!("failed to load {}: {}", path, err));
+    computer.in_buf.extend(args.map(|arg| arg.parse::<intcode::Word>().expect(usage)));
+
+    computer.start_profiling();
+    if let Err(err) = computer.run() {
+        eprintln!("stopped: {}", err);
+    }
+    let profile = computer.stop_profiling().unwrap();
+
+    if folded {
+        print!("{}", profile.to_folded_with(&symbols));
+    } else {
+        print!("{}", profile.to_table_with(computer.memory(), TABLE_ROWS, &symbols));
+    }
+}